@@ -0,0 +1,327 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Shell completion scripts, generated from [`COMMANDS`].
+//!
+//! The scripts complete both `git-home` and `git home`: git's bash and zsh
+//! completion call `_git_home` and `_git-home` for the home subcommand, and
+//! fish's git completion hands `git home` to the `git-home` completions.
+//! Tracked files, branches and remotes are looked up by running
+//! `git-home completions --list <kind>`.
+
+use super::parser::{Opt, HELP};
+use super::{Command, Complete, Shell, COMMANDS};
+
+/// Returns the completion script for `shell`.
+pub fn gen_completions(shell: &Shell) -> String {
+    match shell {
+        Shell::Bash => gen_bash(),
+        Shell::Zsh => gen_zsh(),
+        Shell::Fish => gen_fish(),
+    }
+}
+
+fn command_names() -> String {
+    let names: Vec<&str> = COMMANDS.iter().map(|x| x.name).collect();
+    names.join(" ")
+}
+
+/// Every option of `command` as it is written, such as `-f --force`.
+fn option_words(command: &Command) -> String {
+    let mut words = Vec::new();
+    for i in command.options.iter().chain([&HELP]) {
+        if let Some(short) = i.short {
+            words.push(format!("-{}", short));
+        }
+        if let Some(long) = i.long {
+            words.push(format!("--{}", long));
+        }
+    }
+    words.join(" ")
+}
+
+/// The case arms completing the values of every command. `files` completes
+/// any file, `list` is the function listing candidates of a kind and
+/// `words` completes the words put in place of its `{}`.
+fn value_cases(files: &str, list: &str, words: &str) -> String {
+    let mut cases = String::new();
+    for i in COMMANDS {
+        let action = match i.complete {
+            Complete::Nothing => continue,
+            Complete::Files => String::from(files),
+            Complete::Tracked => format!("{} tracked", list),
+            Complete::Branches => format!("{} branches", list),
+            Complete::Remotes => format!("{} remotes", list),
+            Complete::Words(values) => words.replace("{}", &values.join(" ")),
+        };
+        cases.push_str(&format!("\t{}) {} ;;\n", i.name, action));
+    }
+    cases
+}
+
+fn gen_bash() -> String {
+    let mut options = String::new();
+    for i in COMMANDS {
+        options.push_str(&format!(
+            "\t\t{}) opts=\"{}\" ;;\n",
+            i.name,
+            option_words(i)
+        ));
+    }
+    let values = value_cases(
+        "compopt -o filenames 2>/dev/null; COMPREPLY=($(compgen -f -- \"$cur\"))",
+        "__git_home_list",
+        "(( cword == base + 1 )) && COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+    );
+
+    format!(
+        r#"# bash completion for git home.
+# Load it from ~/.bashrc, after git's completion if you use it:
+#   source <(git home completions bash)
+
+__git_home_list ()
+{{
+	local IFS=$'\n'
+	compopt -o filenames 2>/dev/null
+	COMPREPLY=($(compgen -W "$(git-home completions --list "$1" 2>/dev/null)" -- "$cur"))
+}}
+
+# Called by git's completion for "git home", with words, cword and cur set.
+_git_home ()
+{{
+	local base=2 opts
+	[[ ${{words[0]}} == *git-home ]] && base=1
+	local command=${{words[base]}}
+
+	if (( cword == base )); then
+		COMPREPLY=($(compgen -W "{commands} --help --version" -- "$cur"))
+		return
+	fi
+	if [[ $cur == -* ]]; then
+		case "$command" in
+{options}		esac
+		COMPREPLY=($(compgen -W "$opts" -- "$cur"))
+		return
+	fi
+	case "$command" in
+{values}	esac
+}}
+
+_git_home_standalone ()
+{{
+	local cur=${{COMP_WORDS[COMP_CWORD]}} cword=$COMP_CWORD
+	local words=("${{COMP_WORDS[@]}}")
+	_git_home
+}}
+
+complete -F _git_home_standalone git-home
+"#,
+        commands = command_names(),
+        options = options,
+        values = values,
+    )
+}
+
+/// Quotes `text` for a single quoted zsh or fish string.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn gen_zsh() -> String {
+    let mut options = String::new();
+    for i in COMMANDS {
+        options.push_str(&format!(
+            "\t\t{}) compadd -- {} ;;\n",
+            i.name,
+            option_words(i)
+        ));
+    }
+    let values = value_cases(
+        "_files",
+        "__git_home_list",
+        "(( CURRENT == 3 )) && compadd -- {}",
+    );
+
+    format!(
+        r#"#compdef git-home
+# zsh completion for git home. Save it as _git-home in a directory on
+# $fpath, or load it from ~/.zshrc after compinit:
+#   source <(git home completions zsh)
+
+__git_home_list () {{
+	compadd -f -- ${{(f)"$(git-home completions --list $1 2>/dev/null)"}}
+}}
+
+# zsh's git completion calls this for "git home", with words starting at
+# "home", so the command is always the second word.
+_git-home () {{
+	if (( CURRENT == 2 )); then
+		compadd -- {commands} --help --version
+		return
+	fi
+	local command=$words[2]
+	if [[ $PREFIX == -* ]]; then
+		case $command in
+{options}		esac
+		return
+	fi
+	case $command in
+{values}	esac
+}}
+
+if [[ $funcstack[1] == _git-home ]]; then
+	_git-home "$@"
+else
+	compdef _git-home git-home
+fi
+"#,
+        commands = command_names(),
+        options = options,
+        values = values,
+    )
+}
+
+fn fish_option(command: &str, opt: &Opt) -> String {
+    let mut line = format!("complete -c git-home -n '__git_home_using {}'", command);
+    if let Some(short) = opt.short {
+        line.push_str(&format!(" -s {}", short));
+    }
+    if let Some(long) = opt.long {
+        line.push_str(&format!(" -l {}", long));
+    }
+    if opt.value.is_some() {
+        line.push_str(" -r");
+    }
+    line.push_str(&format!(" -d {}\n", quote(opt.help)));
+    line
+}
+
+fn gen_fish() -> String {
+    let mut lines = String::new();
+    for i in COMMANDS {
+        for opt in i.options.iter().chain([&HELP]) {
+            lines.push_str(&fish_option(i.name, opt));
+        }
+        let values = match i.complete {
+            Complete::Nothing => continue,
+            Complete::Files => String::from("-F"),
+            Complete::Tracked => {
+                String::from("-a '(git-home completions --list tracked 2>/dev/null)'")
+            }
+            Complete::Branches => {
+                String::from("-a '(git-home completions --list branches 2>/dev/null)'")
+            }
+            Complete::Remotes => {
+                String::from("-a '(git-home completions --list remotes 2>/dev/null)'")
+            }
+            Complete::Words(list) => {
+                lines.push_str(&format!(
+                    "complete -c git-home -n '__git_home_using {} && __git_home_first_value' -a {}\n",
+                    i.name,
+                    quote(&list.join(" "))
+                ));
+                continue;
+            }
+        };
+        lines.push_str(&format!(
+            "complete -c git-home -n '__git_home_using {}' {}\n",
+            i.name, values
+        ));
+    }
+
+    format!(
+        r#"# fish completion for git home. Save it as
+# ~/.config/fish/completions/git-home.fish, fish's git completion uses it
+# for "git home" too.
+
+function __git_home_using -a command
+	set -l words (commandline -opc)
+	test (count $words) -ge 2; and test $words[2] = $command
+end
+
+function __git_home_first_value
+	test (count (commandline -opc)) -eq 2
+end
+
+complete -c git-home -f
+complete -c git-home -n 'test (count (commandline -opc)) -eq 1' -a {commands}
+complete -c git-home -n 'test (count (commandline -opc)) -eq 1' -l help -d 'print the usage of git home.'
+complete -c git-home -n 'test (count (commandline -opc)) -eq 1' -l version -d 'print the version of git home.'
+{lines}"#,
+        commands = quote(&command_names()),
+        lines = lines,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The line of `script` completing the options of `command`.
+    fn options_line<'a>(script: &'a str, command: &str) -> &'a str {
+        let arm = format!("\t\t{}) ", command);
+        script
+            .lines()
+            .find(|line| line.starts_with(&arm))
+            .unwrap_or_else(|| panic!("no options for {}", command))
+    }
+
+    #[test]
+    fn bash_and_zsh_complete_every_command() {
+        for (script, list) in [(gen_bash(), "compgen -W \""), (gen_zsh(), "compadd -- ")] {
+            assert!(script.contains(&format!("{}{} --help", list, command_names())));
+            for command in COMMANDS {
+                let line = options_line(&script, command.name);
+                for opt in command.options.iter().chain([&HELP]) {
+                    if let Some(long) = opt.long {
+                        assert!(line.contains(&format!("--{}", long)), "{}", line);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fish_completes_every_command() {
+        let script = gen_fish();
+        assert!(script.contains(&format!("-a {}\n", quote(&command_names()))));
+        for command in COMMANDS {
+            let using = format!("-n '__git_home_using {}'", command.name);
+            for opt in command.options.iter().chain([&HELP]) {
+                if let Some(long) = opt.long {
+                    let option = format!(" -l {}", long);
+                    assert!(
+                        script
+                            .lines()
+                            .any(|line| line.contains(&using) && line.contains(&option)),
+                        "no {} for {}",
+                        option,
+                        command.name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lists_every_command_once() {
+        let names = command_names();
+        for command in COMMANDS {
+            let count = names.split(' ').filter(|x| *x == command.name).count();
+            assert_eq!(count, 1, "{}", command.name);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The man page of git home, in roff.
+//!
+//! It is built from the same tables as `git home --help` and each command's
+//! `--help`, so the two can not disagree.
+
+use super::parser::HELP;
+use super::usage::{GLOBAL_OPTIONS, OTHER_VARIABLES};
+use super::COMMANDS;
+use git_home::config::SETTINGS;
+use git_home::error::EXIT_CODES;
+use git_home::GIT_HOME_CONFIG_FILE;
+
+/// Escapes `text` for use in a roff text line.
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    match text.starts_with(['.', '\'']) {
+        true => format!("\\&{}", text),
+        false => text,
+    }
+}
+
+/// Escapes `text` and starts it with a capital letter.
+fn sentence(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => escape(&format!("{}{}", first.to_uppercase(), chars.as_str())),
+        None => String::new(),
+    }
+}
+
+/// Adds a tagged paragraph with `tag` in bold.
+fn push_item(page: &mut String, tag: &str, text: &str) {
+    page.push_str(&format!(
+        ".TP\n\\fB{}\\fR\n{}\n",
+        escape(tag),
+        sentence(text)
+    ));
+}
+
+fn push_commands(page: &mut String) {
+    page.push_str(".SH COMMANDS\n");
+    for command in COMMANDS {
+        page.push_str(".PP\n");
+        let forms: Vec<String> = command
+            .usage
+            .iter()
+            .map(|form| {
+                format!(
+                    "\\fBgit home {}\\fR",
+                    escape(format!("{} {}", command.name, form).trim_end())
+                )
+            })
+            .collect();
+        page.push_str(&forms.join("\n.br\n"));
+        page.push_str(&format!("\n.RS\n{}\n", sentence(command.summary)));
+        for opt in command.options.iter().chain([&HELP]) {
+            push_item(page, &opt.synopsis(), opt.help);
+        }
+        page.push_str(".RE\n");
+    }
+}
+
+/// Returns the man page of git home.
+pub fn gen_manpage() -> String {
+    let mut page = format!(
+        ".TH GIT\\-HOME 1 \"\" \"{}\" \"Git Home Manual\"\n",
+        escape(concat!("git-home ", env!("CARGO_PKG_VERSION")))
+    );
+    page.push_str(".SH NAME\ngit\\-home \\- a dotfiles manager using git\n");
+    page.push_str(".SH SYNOPSIS\n\\fBgit home\\fR \\fIcommand\\fR [\\fIargs\\fR] [\\fB\\-\\-\\fR \\fIgit\\-command\\fR]\n");
+    page.push_str(
+        ".SH DESCRIPTION\n\
+         git home keeps the configuration files in $HOME in a git repo stored outside\n\
+         of it, so they can be versioned and shared between machines without any\n\
+         symlinking. Options follow the GNU conventions: short flags can be combined\n\
+         and values given as \\fB\\-n 5\\fR, \\fB\\-n5\\fR, \\fB\\-\\-max\\-count 5\\fR or\n\
+         \\fB\\-\\-max\\-count=5\\fR.\n",
+    );
+
+    push_commands(&mut page);
+
+    page.push_str(".SH OPTIONS\n");
+    for (name, text) in GLOBAL_OPTIONS {
+        push_item(&mut page, name, text);
+    }
+
+    page.push_str(&format!(
+        ".SH CONFIGURATION\nSettings are read from \\fI{}\\fR.\n",
+        escape(&format!("$HOME/{}", GIT_HOME_CONFIG_FILE))
+    ));
+    for setting in SETTINGS {
+        let mut text = String::from(setting.description);
+        if !setting.values.is_empty() {
+            text.push_str(&format!(" One of {}.", setting.values.join(", ")));
+        }
+        if let Some(default) = setting.default {
+            text.push_str(&format!(" Defaults to {}.", default));
+        }
+        push_item(&mut page, setting.key, &text);
+    }
+
+    page.push_str(".SH ENVIRONMENT\n");
+    for setting in SETTINGS {
+        let text = format!("overrides the {} setting.", setting.key);
+        push_item(&mut page, &setting.env.join(", "), &text);
+    }
+    for (name, text) in OTHER_VARIABLES {
+        push_item(&mut page, name, text);
+    }
+
+    page.push_str(
+        ".SH EXIT STATUS\n\
+         Errors are printed to stderr. Commands passed through to git exit with\n\
+         git's own code.\n",
+    );
+    push_item(&mut page, "0", "success.");
+    for (code, text) in EXIT_CODES {
+        push_item(&mut page, &code.to_string(), text);
+    }
+
+    page.push_str(".SH SEE ALSO\n\\fBgit\\fR(1)\n");
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_every_command_and_option() {
+        let page = gen_manpage();
+        for command in COMMANDS {
+            let name = format!("\\fBgit home {}", escape(command.name));
+            assert!(page.contains(&name), "{}", command.name);
+            for opt in command.options {
+                let tag = format!(".TP\n\\fB{}\\fR\n", escape(&opt.synopsis()));
+                assert!(page.contains(&tag), "{} {}", command.name, tag);
+            }
+        }
+    }
+
+    #[test]
+    fn lists_settings_and_exit_codes() {
+        let page = gen_manpage();
+        for setting in SETTINGS {
+            assert!(page.contains(&format!("\\fB{}\\fR", escape(setting.key))));
+        }
+        for (code, _) in EXIT_CODES {
+            assert!(page.contains(&format!(".TP\n\\fB{}\\fR\n", code)));
+        }
+    }
+
+    #[test]
+    fn escapes_roff() {
+        assert_eq!(escape("--all"), "\\-\\-all");
+        assert_eq!(escape(".profile"), "\\&.profile");
+        assert_eq!(sentence("a\\b"), "A\\eb");
+    }
+}
@@ -14,16 +14,378 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
 use std::default::Default;
 use std::env;
-use std::env::Args;
 use std::fmt::Debug;
 use std::io;
-use std::path::{Path, PathBuf};
-use std::process::exit;
+use std::path::{Component, Path, PathBuf};
+pub mod completions;
+pub mod manpage;
+pub mod parser;
 pub mod usage;
-use crate::git::{gen_commit_template, strip_commit_template};
-use usage::{print_add_help, print_commit_usage};
+use crate::cli::{
+    edit_message, gen_commit_template, get_home_dir, load_config, resolve_git_repo,
+    strip_commit_template,
+};
+use git2::Repository;
+use git_home::{Context, Environment, Error, GitHome, Result};
+use parser::{parse, Opt, ParsedArgs};
+
+/// A git home command, as described in its help.
+pub struct Command {
+    pub name: &'static str,
+    /// What it does, as listed in the usage and the man page.
+    pub summary: &'static str,
+    /// The forms the command takes, without the leading `git home <name>`.
+    pub usage: &'static [&'static str],
+    /// What its values are completed with.
+    pub complete: Complete,
+    pub options: &'static [Opt],
+}
+
+/// What the shell completes the values of a command with.
+pub enum Complete {
+    Nothing,
+    Files,
+    /// Files tracked by the home repo.
+    Tracked,
+    Branches,
+    Remotes,
+    /// The first value is one of these words.
+    Words(&'static [&'static str]),
+}
+
+/// Every command, in the order they are listed.
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "add",
+        summary: "add a file to the git_home repo. Directories skip ignored files, -f adds ignored files too.",
+        usage: &["[-f] <file>...", "-u"],
+        complete: Complete::Files,
+        options: &[
+            Opt { name: "force", short: Some('f'), long: Some("force"), value: None, help: "add ignored files too." },
+            Opt {
+                name: "update",
+                short: Some('u'),
+                long: Some("update"),
+                value: None,
+                help: "stage the changes to every tracked file.",
+            },
+        ],
+    },
+    Command {
+        name: "rm",
+        summary: "stop tracking a file, leaving it in $HOME. Pass --delete to also remove it from $HOME.",
+        usage: &["[--cached | --delete] <file>..."],
+        complete: Complete::Tracked,
+        options: &[
+            Opt { name: "cached", short: None, long: Some("cached"), value: None, help: "leave the files in $HOME (default)." },
+            Opt { name: "delete", short: None, long: Some("delete"), value: None, help: "also delete the files from $HOME." },
+        ],
+    },
+    Command {
+        name: "diff",
+        summary: "show changes to tracked files. --staged shows changes in the index, <commit> [<commit>] compares against past commits.",
+        usage: &["[--staged] [<commit> [<commit>]] [<file>...]"],
+        complete: Complete::Tracked,
+        options: &[
+            Opt { name: "staged", short: None, long: Some("staged"), value: None, help: "show the changes in the index." },
+            Opt { name: "staged", short: None, long: Some("cached"), value: None, help: "same as --staged." },
+        ],
+    },
+    Command {
+        name: "remote",
+        summary: "list, add, remove or set-url [--push] of remotes.",
+        usage: &["[list]", "add <name> <url>", "remove <name>", "set-url [--push] <name> <url>"],
+        complete: Complete::Words(&["list", "add", "remove", "set-url"]),
+        options: &[Opt { name: "push", short: None, long: Some("push"), value: None, help: "set the push url instead." }],
+    },
+    Command {
+        name: "push",
+        summary: "push the current branch to a remote, defaults to its upstream or the remote setting.",
+        usage: &["[<remote> [<branch>]]"],
+        complete: Complete::Remotes,
+        options: &[],
+    },
+    Command {
+        name: "fetch",
+        summary: "fetch from a remote without touching $HOME.",
+        usage: &["[<remote>]"],
+        complete: Complete::Remotes,
+        options: &[],
+    },
+    Command {
+        name: "pull",
+        summary: "fetch and merge a remote branch into $HOME. Aborts without changing anything if local changes would be overwritten.",
+        usage: &["[<remote> [<branch>]]"],
+        complete: Complete::Remotes,
+        options: &[],
+    },
+    Command {
+        name: "clone",
+        summary: "clone a home repo and check its files out into $HOME, backing up any existing files it would overwrite.",
+        usage: &["<url>"],
+        complete: Complete::Nothing,
+        options: &[],
+    },
+    Command {
+        name: "status",
+        summary: "print the status of tracked files. -u also lists untracked files next to tracked ones. --porcelain [-z] and --json print it for scripts.",
+        usage: &["[-u] [--porcelain [-z] | --json]"],
+        complete: Complete::Nothing,
+        options: &[
+            Opt {
+                name: "untracked",
+                short: Some('u'),
+                long: Some("untracked"),
+                value: None,
+                help: "list untracked files next to tracked ones.",
+            },
+            Opt { name: "porcelain", short: None, long: Some("porcelain"), value: None, help: "print it for scripts." },
+            Opt { name: "json", short: None, long: Some("json"), value: None, help: "print it as JSON." },
+            Opt { name: "null", short: Some('z'), long: None, value: None, help: "end porcelain entries with NUL." },
+        ],
+    },
+    Command {
+        name: "ls-files",
+        summary: "list the tracked files, or those in a commit with <rev>. --tree prints them as a tree, -l adds their mode and size, --modified and --deleted only list files changed in $HOME.",
+        usage: &["[-l] [--tree | -z] [--modified] [--deleted]", "[-l] [--tree | -z] <rev>"],
+        complete: Complete::Branches,
+        options: &[
+            Opt { name: "long", short: Some('l'), long: Some("long"), value: None, help: "show the mode and size of each file." },
+            Opt { name: "tree", short: Some('t'), long: Some("tree"), value: None, help: "print the files as a directory tree." },
+            Opt {
+                name: "modified",
+                short: Some('m'),
+                long: Some("modified"),
+                value: None,
+                help: "only files whose copy in $HOME has changed.",
+            },
+            Opt {
+                name: "deleted",
+                short: Some('d'),
+                long: Some("deleted"),
+                value: None,
+                help: "only files deleted from $HOME.",
+            },
+            Opt {
+                name: "null",
+                short: Some('z'),
+                long: None,
+                value: None,
+                help: "end entries with NUL, with paths relative to $HOME.",
+            },
+        ],
+    },
+    Command {
+        name: "init",
+        summary: "initialize a new home repo.",
+        usage: &[""],
+        complete: Complete::Nothing,
+        options: &[],
+    },
+    Command {
+        name: "commit",
+        summary: "commit current index to repository.",
+        usage: &["[-m <message>]"],
+        complete: Complete::Nothing,
+        options: &[Opt {
+            name: "message",
+            short: Some('m'),
+            long: Some("message"),
+            value: Some("message"),
+            help: "use the message instead of opening the editor. Repeat it for more paragraphs.",
+        }],
+    },
+    Command {
+        name: "log",
+        summary: "prints the commit history, through the pager in a terminal. Takes -n <count>, --oneline, --since <date>, --until <date> and files to filter by.",
+        usage: &["[-n <count>] [--oneline] [--since <date>] [--until <date>] [<file>...]"],
+        complete: Complete::Tracked,
+        options: &[
+            Opt {
+                name: "max-count",
+                short: Some('n'),
+                long: Some("max-count"),
+                value: Some("count"),
+                help: "show at most this many commits.",
+            },
+            Opt { name: "oneline", short: None, long: Some("oneline"), value: None, help: "print one line per commit." },
+            Opt {
+                name: "since",
+                short: None,
+                long: Some("since"),
+                value: Some("date"),
+                help: "only commits made on or after the date.",
+            },
+            Opt { name: "since", short: None, long: Some("after"), value: Some("date"), help: "same as --since." },
+            Opt {
+                name: "until",
+                short: None,
+                long: Some("until"),
+                value: Some("date"),
+                help: "only commits made on or before the date.",
+            },
+            Opt { name: "until", short: None, long: Some("before"), value: Some("date"), help: "same as --until." },
+        ],
+    },
+    Command {
+        name: "show",
+        summary: "show a commit and its changes, or a file at a commit with <rev>:<file>.",
+        usage: &["[<rev> | <rev>:<file>]"],
+        complete: Complete::Nothing,
+        options: &[],
+    },
+    Command {
+        name: "restore",
+        summary: "restore files in $HOME from the index, or a commit with --source <rev>, backing up the current version first. --staged unstages files instead.",
+        usage: &["[--staged] [--source <rev>] <file>..."],
+        complete: Complete::Tracked,
+        options: &[
+            Opt { name: "staged", short: None, long: Some("staged"), value: None, help: "unstage the files instead." },
+            Opt {
+                name: "source",
+                short: Some('s'),
+                long: Some("source"),
+                value: Some("rev"),
+                help: "restore from a commit instead of the index.",
+            },
+        ],
+    },
+    Command {
+        name: "reset",
+        summary: "unstage files, or move HEAD to a commit with --soft or --mixed. --hard also needs --confirm as it rewrites $HOME.",
+        usage: &["<file>...", "[--soft | --mixed] [<rev>]", "--hard --confirm [<rev>]"],
+        complete: Complete::Tracked,
+        options: &[
+            Opt { name: "soft", short: None, long: Some("soft"), value: None, help: "only move HEAD." },
+            Opt { name: "mixed", short: None, long: Some("mixed"), value: None, help: "move HEAD and reset the index." },
+            Opt {
+                name: "hard",
+                short: None,
+                long: Some("hard"),
+                value: None,
+                help: "also reset the files in $HOME, backing up local changes.",
+            },
+            Opt { name: "confirm", short: None, long: Some("confirm"), value: None, help: "allow --hard." },
+        ],
+    },
+    Command {
+        name: "branch",
+        summary: "list, create, delete (-d, -D) or rename (-m) branches.",
+        usage: &["[--list]", "<name> [<start>]", "-d [-f] <name>", "-D <name>", "-m [<old>] <new>"],
+        complete: Complete::Branches,
+        options: &[
+            Opt { name: "list", short: Some('l'), long: Some("list"), value: None, help: "list the branches." },
+            Opt { name: "delete", short: Some('d'), long: Some("delete"), value: None, help: "delete a merged branch." },
+            Opt {
+                name: "force",
+                short: Some('f'),
+                long: Some("force"),
+                value: None,
+                help: "with -d, delete the branch even if it is not merged.",
+            },
+            Opt { name: "force-delete", short: Some('D'), long: None, value: None, help: "same as -d -f." },
+            Opt { name: "move", short: Some('m'), long: Some("move"), value: None, help: "rename a branch." },
+        ],
+    },
+    Command {
+        name: "switch",
+        summary: "check out a branch into $HOME, -c creates it first.",
+        usage: &["[-c] <branch>"],
+        complete: Complete::Branches,
+        options: &[Opt {
+            name: "create",
+            short: Some('c'),
+            long: Some("create"),
+            value: None,
+            help: "create the branch at HEAD first.",
+        }],
+    },
+    Command {
+        name: "profile",
+        summary: "show the host profile for this machine, or list them all.",
+        usage: &["[show | list]"],
+        complete: Complete::Words(&["show", "list"]),
+        options: &[],
+    },
+    Command {
+        name: "template",
+        summary: "list, add or remove files rendered with machine specific values, or render them all again.",
+        usage: &["[list]", "add <file>...", "remove <file>...", "render"],
+        complete: Complete::Words(&["list", "add", "remove", "render"]),
+        options: &[],
+    },
+    Command {
+        name: "secrets",
+        summary: "list, add or remove patterns of files to encrypt, or rekey them.",
+        usage: &["[list]", "add <pattern>...", "remove <pattern>...", "rekey"],
+        complete: Complete::Words(&["list", "add", "remove", "rekey"]),
+        options: &[],
+    },
+    Command {
+        name: "ignore",
+        summary: "list, add or remove ignore patterns, or explain why a file is ignored.",
+        usage: &["[list]", "add <pattern>...", "remove <pattern>...", "explain <file>..."],
+        complete: Complete::Words(&["list", "add", "remove", "explain"]),
+        options: &[],
+    },
+    Command {
+        name: "config",
+        summary: "list the settings, or get or set one.",
+        usage: &["[list]", "get <key>", "set <key> <value>"],
+        complete: Complete::Words(&["list", "get", "set"]),
+        options: &[],
+    },
+    Command {
+        name: "doctor",
+        summary: "check the home repo and its settings for problems, and suggest fixes.",
+        usage: &[""],
+        complete: Complete::Nothing,
+        options: &[],
+    },
+    Command {
+        name: "completions",
+        summary: "prints the completion script for bash, zsh or fish.",
+        usage: &["bash | zsh | fish"],
+        complete: Complete::Words(&["bash", "zsh", "fish"]),
+        options: &[Opt {
+            name: "list",
+            short: None,
+            long: Some("list"),
+            value: Some("tracked | branches | remotes"),
+            help: "print the candidates the scripts complete with.",
+        }],
+    },
+    Command {
+        name: "manpage",
+        summary: "prints the man page of git home.",
+        usage: &[""],
+        complete: Complete::Nothing,
+        options: &[],
+    },
+];
+
+/// Looks up the command called `name`.
+pub fn find_command(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|x| x.name == name)
+}
+
+/// The error for arguments that fit none of the forms of `command`.
+fn usage_error(command: &str) -> Error {
+    let forms = find_command(command).map(|x| x.usage).unwrap_or_default();
+    let lines: Vec<String> = forms
+        .iter()
+        .map(|form| {
+            format!("git home {} {}", command, form)
+                .trim_end()
+                .to_string()
+        })
+        .collect();
+    Error::Usage(format!("Usage: {}", lines.join("\n   or: ")))
+}
+
 #[derive(Debug, PartialEq)]
 pub enum AddMode {
     Normal,
@@ -34,200 +396,1036 @@ pub enum AddMode {
 pub struct AddArgs {
     pub mode: AddMode,
     pub values: Vec<String>,
+    /// Add files even if they are ignored.
+    pub force: bool,
 }
 
 impl AddArgs {
-    pub fn new(args: Vec<String>) -> AddArgs {
-        let mut args = args.iter();
-        let temp_args = match args.next() {
-            Some(val) => String::from(&*val),
-            None => "".to_string(),
-        };
-        let mode;
-        let values: Vec<String>;
-        if temp_args.len() > 10 && &temp_args[0..9] == "--update" {
-            values = args.map(|x| String::from(x)).collect();
-            mode = AddMode::All;
-        } else if temp_args == "-u" {
-            values = args.map(|x| String::from(x)).collect();
-            mode = AddMode::All;
-        } else {
-            values = args.map(|x| canonicalize_file_path(&x)).collect();
-            mode = AddMode::Normal;
+    pub fn new(args: ParsedArgs) -> Result<AddArgs> {
+        let force = args.flag("force");
+        let mode = match (args.flag("update"), args.values.is_empty()) {
+            (true, true) => AddMode::All,
+            (false, false) => AddMode::Normal,
+            _ => return Err(usage_error("add")),
+        };
+        let values = args
+            .values
+            .iter()
+            .map(|x| canonicalize_file_path(x))
+            .collect::<Result<_>>()?;
+
+        Ok(AddArgs {
+            mode,
+            values,
+            force,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CommitArgs {
+    pub message: String,
+}
+
+impl CommitArgs {
+    pub fn new(args: ParsedArgs) -> Result<CommitArgs> {
+        let messages = args.all("message");
+        let message = match (messages.is_empty(), args.values.as_slice()) {
+            // As in git, every -m is its own paragraph.
+            (false, []) => messages.join("\n\n"),
+            // A message without -m is still accepted.
+            (true, [message]) => message.clone(),
+            (true, []) => {
+                let template = gen_commit_template()?;
+                strip_commit_template(edit_message(template)?)
+            }
+            _ => return Err(usage_error("commit")),
         };
 
-        AddArgs { mode, values }
+        Ok(CommitArgs { message })
     }
 }
 
 #[derive(Debug, PartialEq)]
-pub enum CommitMode {
-    Commit,
+pub enum RmMode {
+    /// Only remove the paths from the index, leaving the files in $HOME.
+    Cached,
+    /// Remove the paths from the index and delete them from $HOME.
+    Delete,
 }
 
 #[derive(Debug, PartialEq)]
-pub struct CommitArgs {
-    pub mode: CommitMode,
+pub struct RmArgs {
+    pub mode: RmMode,
     pub values: Vec<String>,
 }
 
-impl CommitArgs {
-    pub fn new(args: Vec<String>) -> io::Result<CommitArgs> {
-        let mut args = args.iter();
-        let temp_args = match args.next() {
-            Some(val) => String::from(&*val),
-            None => {
-                let template = gen_commit_template();
-                strip_commit_template(edit::edit(template)?)
+impl RmArgs {
+    pub fn new(args: ParsedArgs) -> Result<RmArgs> {
+        // The last of --cached and --delete wins.
+        let mode = match args.options.last() {
+            Some(("delete", _)) => RmMode::Delete,
+            _ => RmMode::Cached,
+        };
+        if args.values.is_empty() {
+            return Err(Error::Usage(String::from(
+                "git home rm requires at least one file.",
+            )));
+        }
+        let values = args
+            .values
+            .iter()
+            .map(|x| canonicalize_file_path(x))
+            .collect::<Result<_>>()?;
+
+        Ok(RmArgs { mode, values })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiffMode {
+    /// Changes in $HOME that have not been added to the index.
+    Workdir,
+    /// Changes in the index that have not been commited.
+    Staged,
+    /// Changes between a commit and $HOME, or between two commits.
+    Commits(String, Option<String>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DiffArgs {
+    pub mode: DiffMode,
+    pub color: bool,
+    pub paths: Vec<String>,
+}
+
+impl DiffArgs {
+    pub fn new(args: ParsedArgs) -> Result<DiffArgs> {
+        let staged = args.flag("staged");
+        let mut revs = Vec::new();
+        let mut paths = Vec::new();
+        let repo = open_repo_quietly();
+        for arg in args.values {
+            match resolve_rev_or_path(repo.as_ref(), &arg)? {
+                RevOrPath::Rev(rev) => revs.push(rev),
+                RevOrPath::Path(path) => paths.push(path),
+            }
+        }
+
+        let mut revs = revs.into_iter();
+        let mode = match (staged, revs.next(), revs.next(), revs.next()) {
+            (false, None, _, _) => DiffMode::Workdir,
+            (true, None, _, _) => DiffMode::Staged,
+            (false, Some(from), to, None) => DiffMode::Commits(from, to),
+            _ => {
+                return Err(Error::Usage(String::from(
+                    "git home diff takes either --staged or up to two commits.",
+                )));
             }
         };
 
-        let mode;
-        let values: Vec<String>;
-        if temp_args.len() > 10 && &temp_args[0..9] == "--message" {
-            let mut temp_args = temp_args.split('=');
-            temp_args.next();
-            values = temp_args.map(|x| String::from(x)).collect();
-            mode = CommitMode::Commit;
-        } else if temp_args == "-m" {
-            values = args.map(|x| String::from(x)).collect();
-            mode = CommitMode::Commit;
-        } else {
-            values = vec![temp_args];
-            mode = CommitMode::Commit;
+        Ok(DiffArgs {
+            mode,
+            color: has_color(),
+            paths,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RemoteMode {
+    List,
+    Add(String, String),
+    Remove(String),
+    /// Sets the fetch url, or the push url when the flag is set.
+    SetUrl(String, String, bool),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RemoteArgs {
+    pub mode: RemoteMode,
+}
+
+impl RemoteArgs {
+    pub fn new(args: ParsedArgs) -> Result<RemoteArgs> {
+        let push = args.flag("push");
+        let mut values = args.values.into_iter();
+        let command = values.next().unwrap_or_default();
+        let values: Vec<String> = values.collect();
+
+        let mode = match (command.as_str(), values.as_slice(), push) {
+            ("" | "list", [], false) => RemoteMode::List,
+            ("add", [name, url], false) => RemoteMode::Add(name.clone(), url.clone()),
+            ("remove" | "rm", [name], false) => RemoteMode::Remove(name.clone()),
+            ("set-url", [name, url], _) => RemoteMode::SetUrl(name.clone(), url.clone(), push),
+            _ => return Err(usage_error("remote")),
         };
 
-        Ok(CommitArgs { mode, values })
+        Ok(RemoteArgs { mode })
     }
 }
 
-/// Holds the program paramaters
 #[derive(Debug, PartialEq)]
+pub struct PushArgs {
+    pub remote: Option<String>,
+    pub branch: Option<String>,
+}
+
+impl PushArgs {
+    pub fn new(args: ParsedArgs) -> Result<PushArgs> {
+        let mut values = args.values.into_iter();
+        let remote = values.next();
+        let branch = values.next();
+        if values.next().is_some() {
+            return Err(usage_error("push"));
+        }
+
+        Ok(PushArgs { remote, branch })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FetchArgs {
+    pub remote: Option<String>,
+}
+
+impl FetchArgs {
+    pub fn new(args: ParsedArgs) -> Result<FetchArgs> {
+        let mut values = args.values.into_iter();
+        let remote = values.next();
+        if values.next().is_some() {
+            return Err(usage_error("fetch"));
+        }
+
+        Ok(FetchArgs { remote })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PullArgs {
+    pub remote: Option<String>,
+    pub branch: Option<String>,
+}
+
+impl PullArgs {
+    pub fn new(args: ParsedArgs) -> Result<PullArgs> {
+        let mut values = args.values.into_iter();
+        let remote = values.next();
+        let branch = values.next();
+        if values.next().is_some() {
+            return Err(usage_error("pull"));
+        }
+
+        Ok(PullArgs { remote, branch })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LogArgs {
+    pub count: Option<usize>,
+    pub oneline: bool,
+    /// Only show commits made at or after this unix timestamp.
+    pub since: Option<i64>,
+    /// Only show commits made at or before this unix timestamp.
+    pub until: Option<i64>,
+    pub paths: Vec<String>,
+}
+
+impl LogArgs {
+    pub fn new(args: ParsedArgs) -> Result<LogArgs> {
+        let count = match args.value("max-count") {
+            Some(count) => match count.parse() {
+                Ok(count) => Some(count),
+                Err(_) => return Err(Error::Usage(format!("-n takes a number, not {}.", count))),
+            },
+            None => None,
+        };
+        let parse = |name| args.value(name).map(|x| parse_date(&x)).transpose();
+
+        Ok(LogArgs {
+            count,
+            oneline: args.flag("oneline"),
+            since: parse("since")?,
+            until: parse("until")?,
+            paths: args
+                .values
+                .iter()
+                .map(|x| canonicalize_file_path(x))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ShowArgs {
+    pub rev: String,
+    /// The file to print the contents of at `rev`, relative to $HOME.
+    pub path: Option<String>,
+    pub color: bool,
+}
+
+impl ShowArgs {
+    pub fn new(args: ParsedArgs) -> Result<ShowArgs> {
+        let mut values = args.values.into_iter();
+        let arg = values.next().unwrap_or_else(|| String::from("HEAD"));
+        if values.next().is_some() {
+            return Err(usage_error("show"));
+        }
+        let (rev, path) = match arg.split_once(':') {
+            Some((rev, path)) => (String::from(rev), Some(resolve_home_path(path)?)),
+            None => (arg, None),
+        };
+
+        Ok(ShowArgs {
+            rev,
+            path,
+            color: has_color(),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RestoreArgs {
+    /// The revision to restore from, defaults to the index.
+    pub source: Option<String>,
+    /// Reset the index entries to HEAD instead of touching $HOME.
+    pub staged: bool,
+    pub paths: Vec<String>,
+}
+
+impl RestoreArgs {
+    pub fn new(args: ParsedArgs) -> Result<RestoreArgs> {
+        if args.values.is_empty() {
+            return Err(usage_error("restore"));
+        }
+
+        Ok(RestoreArgs {
+            source: args.value("source"),
+            staged: args.flag("staged"),
+            paths: args
+                .values
+                .iter()
+                .map(|x| resolve_home_path(x))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ResetMode {
+    /// Reset the index entries of the given paths.
+    Paths(Vec<String>),
+    Soft,
+    Mixed,
+    /// Only allowed with --confirm since it rewrites files in $HOME.
+    Hard,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ResetArgs {
+    pub mode: ResetMode,
+    pub rev: String,
+}
+
+impl ResetArgs {
+    pub fn new(args: ParsedArgs) -> Result<ResetArgs> {
+        // The last of --soft, --mixed and --hard wins.
+        let kind = args.options.iter().rev().find_map(|(name, _)| match *name {
+            "soft" => Some(ResetMode::Soft),
+            "mixed" => Some(ResetMode::Mixed),
+            "hard" => Some(ResetMode::Hard),
+            _ => None,
+        });
+        let confirmed = args.flag("confirm");
+        let mut rev = None;
+        let mut paths = Vec::new();
+        let repo = open_repo_quietly();
+        for arg in args.values {
+            match resolve_rev_or_path(repo.as_ref(), &arg)? {
+                RevOrPath::Path(path) => paths.push(path),
+                RevOrPath::Rev(arg) if rev.is_none() => rev = Some(arg),
+                RevOrPath::Rev(_) => {
+                    return Err(Error::Usage(String::from(
+                        "git home reset takes at most one revision.",
+                    )));
+                }
+            }
+        }
+
+        let mode = match (kind, paths.is_empty()) {
+            (None, false) => ResetMode::Paths(paths),
+            (None, true) => ResetMode::Mixed,
+            (Some(_), false) => {
+                return Err(Error::Usage(String::from(
+                    "git home reset cannot combine --soft, --mixed or --hard with files.",
+                )));
+            }
+            (Some(ResetMode::Hard), true) if !confirmed => {
+                return Err(Error::Usage(String::from(
+                    "git home reset --hard overwrites files in your home directory.\nRun it again with --confirm if you are sure.",
+                )));
+            }
+            (Some(kind), true) => kind,
+        };
+
+        Ok(ResetArgs {
+            mode,
+            rev: rev.unwrap_or_else(|| String::from("HEAD")),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BranchMode {
+    List,
+    /// Creates a branch at the given start point, or HEAD.
+    Create(String, Option<String>),
+    /// Deletes a branch, even if it is not merged when the flag is set.
+    Delete(String, bool),
+    /// Renames a branch, or the current branch if no old name is given.
+    Rename(Option<String>, String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BranchArgs {
+    pub mode: BranchMode,
+}
+
+impl BranchArgs {
+    pub fn new(args: ParsedArgs) -> Result<BranchArgs> {
+        let delete = args.flag("delete") || args.flag("force-delete");
+        let force = args.flag("force") || args.flag("force-delete");
+        let action = (args.flag("list"), delete, args.flag("move"), force);
+
+        let mode = match (action, args.values.as_slice()) {
+            ((_, false, false, false), []) => BranchMode::List,
+            ((false, true, false, _), [name]) => BranchMode::Delete(name.clone(), force),
+            ((false, false, true, false), [new]) => BranchMode::Rename(None, new.clone()),
+            ((false, false, true, false), [old, new]) => {
+                BranchMode::Rename(Some(old.clone()), new.clone())
+            }
+            ((false, false, false, false), [name]) => BranchMode::Create(name.clone(), None),
+            ((false, false, false, false), [name, start]) => {
+                BranchMode::Create(name.clone(), Some(start.clone()))
+            }
+            _ => return Err(usage_error("branch")),
+        };
+
+        Ok(BranchArgs { mode })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SwitchArgs {
+    pub branch: String,
+    /// Create the branch at HEAD before switching to it.
+    pub create: bool,
+}
+
+impl SwitchArgs {
+    pub fn new(args: ParsedArgs) -> Result<SwitchArgs> {
+        let create = args.flag("create");
+        match args.values.as_slice() {
+            [branch] => Ok(SwitchArgs {
+                branch: branch.clone(),
+                create,
+            }),
+            _ => Err(usage_error("switch")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TemplateMode {
+    List,
+    Add(Vec<String>),
+    Remove(Vec<String>),
+    /// Rewrites every template in $HOME from the index.
+    Render,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TemplateArgs {
+    pub mode: TemplateMode,
+}
+
+impl TemplateArgs {
+    pub fn new(args: ParsedArgs) -> Result<TemplateArgs> {
+        let mut values = args.values.into_iter();
+        let command = values.next().unwrap_or_default();
+        let paths: Vec<String> = values
+            .map(|x| resolve_home_path(&x))
+            .collect::<Result<_>>()?;
+
+        let mode = match (command.as_str(), paths.is_empty()) {
+            ("" | "list", true) => TemplateMode::List,
+            ("add", false) => TemplateMode::Add(paths),
+            ("remove" | "rm", false) => TemplateMode::Remove(paths),
+            ("render", true) => TemplateMode::Render,
+            _ => return Err(usage_error("template")),
+        };
+
+        Ok(TemplateArgs { mode })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SecretsMode {
+    List,
+    Add(Vec<String>),
+    Remove(Vec<String>),
+    /// Re-encrypts every secret in the index with a new key.
+    Rekey,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SecretsArgs {
+    pub mode: SecretsMode,
+}
+
+impl SecretsArgs {
+    pub fn new(args: ParsedArgs) -> Result<SecretsArgs> {
+        let mut values = args.values.into_iter();
+        let command = values.next().unwrap_or_default();
+        // Patterns with wildcards are kept as written, anything else is a path.
+        let patterns: Vec<String> = values
+            .map(|x| match x.contains(['*', '?']) {
+                true => Ok(String::from(x.strip_prefix("~/").unwrap_or(&x))),
+                false => resolve_home_path(&x),
+            })
+            .collect::<Result<_>>()?;
+
+        let mode = match (command.as_str(), patterns.is_empty()) {
+            ("" | "list", true) => SecretsMode::List,
+            ("add", false) => SecretsMode::Add(patterns),
+            ("remove" | "rm", false) => SecretsMode::Remove(patterns),
+            ("rekey", true) => SecretsMode::Rekey,
+            _ => return Err(usage_error("secrets")),
+        };
+
+        Ok(SecretsArgs { mode })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IgnoreMode {
+    List,
+    Add(Vec<String>),
+    Remove(Vec<String>),
+    /// Shows which pattern, if any, ignores each path.
+    Explain(Vec<String>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct IgnoreArgs {
+    pub mode: IgnoreMode,
+}
+
+impl IgnoreArgs {
+    pub fn new(args: ParsedArgs) -> Result<IgnoreArgs> {
+        let mut values = args.values.into_iter();
+        let command = values.next().unwrap_or_default();
+        let values: Vec<String> = values.collect();
+        // Patterns are relative to $HOME, so ~/ anchors them there.
+        let patterns = || {
+            values
+                .iter()
+                .map(|x| match x.strip_prefix("~/") {
+                    Some(rest) => format!("/{}", rest),
+                    None => x.clone(),
+                })
+                .collect()
+        };
+
+        let mode = match (command.as_str(), values.is_empty()) {
+            ("" | "list", true) => IgnoreMode::List,
+            ("add", false) => IgnoreMode::Add(patterns()),
+            ("remove" | "rm", false) => IgnoreMode::Remove(patterns()),
+            ("explain", false) => IgnoreMode::Explain(
+                values
+                    .iter()
+                    .map(|x| resolve_home_path(x))
+                    .collect::<Result<_>>()?,
+            ),
+            _ => return Err(usage_error("ignore")),
+        };
+
+        Ok(IgnoreArgs { mode })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigMode {
+    /// Lists every setting with its effective value and where it came from.
+    List,
+    Get(String),
+    Set(String, String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ConfigArgs {
+    pub mode: ConfigMode,
+}
+
+impl ConfigArgs {
+    pub fn new(args: ParsedArgs) -> Result<ConfigArgs> {
+        let mode = match args.values.as_slice() {
+            [] => ConfigMode::List,
+            [command] if command == "list" => ConfigMode::List,
+            [command, key] if command == "get" => ConfigMode::Get(key.clone()),
+            [command, key, value] if command == "set" => {
+                ConfigMode::Set(key.clone(), value.clone())
+            }
+            _ => return Err(usage_error("config")),
+        };
+
+        Ok(ConfigArgs { mode })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CompletionsMode {
+    /// Prints the completion script for the shell.
+    Script(Shell),
+    /// Prints the files tracked by the home repo.
+    Tracked,
+    Branches,
+    Remotes,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CompletionsArgs {
+    pub mode: CompletionsMode,
+}
+
+impl CompletionsArgs {
+    pub fn new(args: ParsedArgs) -> Result<CompletionsArgs> {
+        let mode = match (args.value("list").as_deref(), args.values.as_slice()) {
+            (None, [shell]) => match shell.as_str() {
+                "bash" => CompletionsMode::Script(Shell::Bash),
+                "zsh" => CompletionsMode::Script(Shell::Zsh),
+                "fish" => CompletionsMode::Script(Shell::Fish),
+                _ => {
+                    return Err(Error::Usage(format!(
+                        "Can not complete for {}, try bash, zsh or fish.",
+                        shell
+                    )))
+                }
+            },
+            (Some("tracked"), []) => CompletionsMode::Tracked,
+            (Some("branches"), []) => CompletionsMode::Branches,
+            (Some("remotes"), []) => CompletionsMode::Remotes,
+            _ => return Err(usage_error("completions")),
+        };
+
+        Ok(CompletionsArgs { mode })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LsFilesArgs {
+    /// List the files in this commit instead of the index.
+    pub rev: Option<String>,
+    /// Print the files as a directory tree.
+    pub tree: bool,
+    /// Show the mode and size of each file.
+    pub long: bool,
+    /// Only list files with unstaged changes in $HOME.
+    pub modified: bool,
+    /// Only list files deleted from $HOME.
+    pub deleted: bool,
+    /// End entries with NUL instead of a newline.
+    pub null_terminated: bool,
+}
+
+impl LsFilesArgs {
+    pub fn new(args: ParsedArgs) -> Result<LsFilesArgs> {
+        let rev = match args.values.as_slice() {
+            [] => None,
+            [rev] => Some(rev.clone()),
+            _ => return Err(usage_error("ls-files")),
+        };
+        let (modified, deleted) = (args.flag("modified"), args.flag("deleted"));
+        // Changes in $HOME are against the index, not an older commit.
+        if rev.is_some() && (modified || deleted) {
+            return Err(usage_error("ls-files"));
+        }
+        let (tree, null_terminated) = (args.flag("tree"), args.flag("null"));
+        if tree && null_terminated {
+            return Err(Error::Usage(String::from("-z can not be used with --tree")));
+        }
+
+        Ok(LsFilesArgs {
+            rev,
+            tree,
+            long: args.flag("long"),
+            modified,
+            deleted,
+            null_terminated,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StatusFormat {
+    Human,
+    /// A stable line based format for scripts.
+    Porcelain,
+    Json,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StatusArgs {
+    pub format: StatusFormat,
+    pub color: bool,
+    /// List untracked files in directories that hold tracked files.
+    pub untracked: bool,
+    /// End porcelain entries with NUL instead of a newline.
+    pub null_terminated: bool,
+}
+
+impl StatusArgs {
+    pub fn new(args: ParsedArgs) -> Result<StatusArgs> {
+        if !args.values.is_empty() {
+            return Err(usage_error("status"));
+        }
+        let null_terminated = args.flag("null");
+        let mut format = match (args.flag("porcelain"), args.flag("json")) {
+            (false, false) => StatusFormat::Human,
+            (true, false) => StatusFormat::Porcelain,
+            (false, true) => StatusFormat::Json,
+            (true, true) => {
+                return Err(Error::Usage(String::from(
+                    "--porcelain can not be used with --json",
+                )));
+            }
+        };
+        // As in git, -z implies --porcelain.
+        if null_terminated && format == StatusFormat::Human {
+            format = StatusFormat::Porcelain;
+        }
+        if null_terminated && format == StatusFormat::Json {
+            return Err(Error::Usage(String::from("-z can not be used with --json")));
+        }
+
+        Ok(StatusArgs {
+            color: format == StatusFormat::Human && has_color(),
+            format,
+            untracked: args.flag("untracked"),
+            null_terminated,
+        })
+    }
+}
+
+/// Holds the program paramaters
+#[derive(Debug, PartialEq, Default)]
 pub enum ProgMode {
     Add(AddArgs),
+    Rm(RmArgs),
+    Diff(DiffArgs),
+    Remote(RemoteArgs),
+    Push(PushArgs),
+    Fetch(FetchArgs),
+    Pull(PullArgs),
+    Clone(String),
+    Show(ShowArgs),
+    Restore(RestoreArgs),
+    Reset(ResetArgs),
+    Branch(BranchArgs),
+    Switch(SwitchArgs),
+    /// Shows the active host profile, or lists every profile when set.
+    Profile(bool),
+    Template(TemplateArgs),
+    Secrets(SecretsArgs),
+    Ignore(IgnoreArgs),
+    Config(ConfigArgs),
+    Doctor,
+    Completions(CompletionsArgs),
+    Manpage,
+    LsFiles(LsFilesArgs),
     Init,
-    Status(bool),
+    Status(StatusArgs),
     Commit(CommitArgs),
-    Log,
-    Help,
+    Log(LogArgs),
+    /// Prints the help of the command, or the usage of git home without one.
+    Help(Option<&'static str>),
+    Version,
+    #[default]
     None,
-    Passthrough(Option<Box<ProgMode>>, Vec<String>)
+    Passthrough(Option<Box<ProgMode>>, Vec<String>),
 }
 
-impl Default for ProgMode {
-    fn default() -> ProgMode {
-        ProgMode::None
-    }
-}
+fn canonicalize_file_path(init_path: &str) -> Result<String> {
+    let paths = match Path::new(init_path).canonicalize() {
+        Ok(paths) => paths,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::NotFound(format!("{} does not exist.", init_path)));
+        }
+        Err(e) => return Err(e).context(format!("Couldn't canonicalize {}", init_path)),
+    };
+    let mut path_iter = paths.iter();
 
-fn canonicalize_file_path<'a>(init_path: &str) -> String {
-    match Path::new(init_path).canonicalize() {
-        Ok(paths) => {
-            let mut path_iter = paths.iter();
+    // Clear /root/home from PathBuf
+    path_iter.next();
+    path_iter.next();
 
-            // Clear /root/home from PathBuf
-            path_iter.next();
-            path_iter.next();
+    let user = match path_iter.next() {
+        Some(user) => user,
+        None => return Err(Error::OutsideHome(init_path.to_string())),
+    };
+    let env_user = env::var("USER").map_err(|_| Error::MissingEnv("USER"))?;
+    if user.to_str() != Some(env_user.as_str()) {
+        return Err(Error::OutsideHome(init_path.to_string()));
+    }
 
-            let user = match path_iter.next() {
-                Some(user) => user,
-                None => {
-                    eprintln!("Cannot use git home at top level of file system.");
-                    exit(74);
-                }
-            };
-            let env_user = match env::var("USER") {
-                Ok(u) => u,
-                Err(_e) => panic!("$USER not set."),
+    let buf: PathBuf = path_iter.collect();
+    Ok(buf.to_string_lossy().into_owned())
+}
+/// Parses a date given on the command line into a unix timestamp.
+///
+/// Accepts `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS`, RFC 3339 and relative dates
+/// such as `2 weeks ago`.
+fn parse_date(value: &str) -> Result<i64> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.timestamp());
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        });
+    if let Some(naive) = naive {
+        if let Some(date) = Local.from_local_datetime(&naive).earliest() {
+            return Ok(date.timestamp());
+        }
+    }
+
+    let words: Vec<&str> = value.split_whitespace().collect();
+    if let [count, unit, "ago"] = words.as_slice() {
+        if let Ok(count) = count.parse::<i64>() {
+            let duration = match unit.trim_end_matches('s') {
+                "second" => Some(Duration::seconds(count)),
+                "minute" => Some(Duration::minutes(count)),
+                "hour" => Some(Duration::hours(count)),
+                "day" => Some(Duration::days(count)),
+                "week" => Some(Duration::weeks(count)),
+                _ => None,
             };
-            if user.to_str().unwrap() != env_user {
-                eprintln!("git home should only be used on files in the users own home directory");
-                exit(64);
+            if let Some(duration) = duration {
+                return Ok((Local::now() - duration).timestamp());
             }
-
-            let buf: PathBuf = path_iter.collect();
-            let paths = buf.as_path();
-            String::from(match paths.to_str() {
-                Some(s) => s,
-                None => {
-                    eprintln!("could not convert path to string");
-                    exit(74);
-                }
-            })
         }
+    }
+
+    Err(Error::Usage(format!("Could not parse date: {}", value)))
+}
 
-        Err(e) => {
-            eprintln!("Couldn't canonicalize path: {}", e);
-            exit(74);
+/// Returns true if output should be colored. With the color setting on
+/// auto, that is when the terminal advertises color support.
+pub fn has_color() -> bool {
+    let color = load_config().ok().and_then(|config| config.get("color"));
+    match color.as_ref().map(|(value, _)| value.as_str()) {
+        Some("always") => true,
+        Some("never") => false,
+        _ => matches!(
+            env::var("COLORTERM").as_deref(),
+            Ok("truecolor") | Ok("24bit")
+        ),
+    }
+}
+
+/// Turns a path given on the command line into a path relative to $HOME.
+///
+/// Unlike `canonicalize_file_path` this also accepts files that no longer
+/// exist. As they can not be looked up, they are taken to be relative to
+/// $HOME and must not lead out of it.
+fn resolve_home_path(path: &str) -> Result<String> {
+    let home_dir = get_home_dir()?;
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => format!("{}/{}", home_dir, rest),
+        None => String::from(path),
+    };
+    if Path::new(&path).exists() {
+        return canonicalize_file_path(&path);
+    }
+    let mut resolved = PathBuf::new();
+    for component in Path::new(&home_dir).join(&path).components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
         }
     }
+    match resolved.strip_prefix(&home_dir) {
+        Ok(rest) if !rest.as_os_str().is_empty() => Ok(rest.to_string_lossy().into_owned()),
+        _ => Err(Error::OutsideHome(path)),
+    }
+}
+
+/// A value given to diff or reset, once it is known what it names.
+enum RevOrPath {
+    Rev(String),
+    /// A path relative to $HOME.
+    Path(String),
+}
+
+/// Opens the home repo without offering to create it, for telling
+/// revisions and paths apart.
+fn open_repo_quietly() -> Option<Repository> {
+    let home = GitHome::open(
+        resolve_git_repo().ok()?,
+        get_home_dir().ok()?,
+        Environment::default(),
+    )
+    .ok()?;
+    Some(home.into_repo())
 }
+
+/// Decides whether `value` names a revision or a path. As in git a revision
+/// wins, and a path has to exist or be tracked, so deleted files can still
+/// be named.
+fn resolve_rev_or_path(repo: Option<&Repository>, value: &str) -> Result<RevOrPath> {
+    if repo.is_some_and(|repo| repo.revparse_single(value).is_ok()) {
+        return Ok(RevOrPath::Rev(String::from(value)));
+    }
+    let path = resolve_home_path(value)?;
+    let tracked = repo
+        .and_then(|repo| repo.index().ok())
+        .is_some_and(|index| {
+            let dir = format!("{}/", path);
+            index.iter().any(|entry| {
+                entry.path == path.as_bytes() || entry.path.starts_with(dir.as_bytes())
+            })
+        });
+    if tracked || Path::new(value).exists() {
+        return Ok(RevOrPath::Path(path));
+    }
+    Err(Error::NotFound(format!(
+        "{} is neither a revision nor a tracked file.",
+        value
+    )))
+}
+
 fn scan_for_passthrough(args: Vec<String>) -> (Option<Vec<String>>, Vec<String>) {
     let mut prog_args: Vec<String> = Vec::new();
     let mut pass_args: Vec<String> = Vec::new();
     let mut mode = None;
 
     for i in args {
-	if let Some(()) = mode {
-	    pass_args.push(i);
-	} else {
-	    if i == "--" {
-		mode = Some(());
-	    } else {
-		prog_args.push(i);
-	    }
-	}
-    }
-
-    if pass_args.len() == 0 {
-	(None, prog_args)
+        if let Some(()) = mode {
+            pass_args.push(i);
+        } else {
+            if i == "--" {
+                mode = Some(());
+            } else {
+                prog_args.push(i);
+            }
+        }
+    }
+
+    if pass_args.is_empty() {
+        (None, prog_args)
     } else {
-	(Some(pass_args), prog_args)
+        (Some(pass_args), prog_args)
     }
 }
 /// Returns the formated progam arguments
-pub fn format_args() -> io::Result<ProgMode> {
-    let mut mode = ProgMode::None;
+pub fn format_args() -> Result<ProgMode> {
     let mut prog_args = env::args();
     // Clear the binary location from the arguments iterator.
     prog_args.next();
 
     let (passthroughp, prog_args) = scan_for_passthrough(prog_args.collect());
-    let mut prog_args = prog_args.iter();
-    let temp_mode = match prog_args.next() {
-        Some(mode) => mode,
-        None => "",
+    let mut prog_args = prog_args.into_iter();
+    let name = prog_args.next().unwrap_or_default();
+    let mode = match name.as_str() {
+        "" => ProgMode::None,
+        "-h" | "--help" => ProgMode::Help(None),
+        "-V" | "--version" => ProgMode::Version,
+        _ => {
+            let command = match find_command(&name) {
+                Some(command) => command,
+                None => {
+                    return Err(Error::Usage(format!(
+                        "Unknown command {}.\nRun 'git home --help' to see the commands.",
+                        name
+                    )));
+                }
+            };
+            let args = parse(command.name, prog_args.collect(), command.options)?;
+            match args.flag("help") {
+                true => ProgMode::Help(Some(command.name)),
+                false => command_mode(command.name, args)?,
+            }
+        }
+    };
+
+    match passthroughp {
+        Some(vec) if mode == ProgMode::None => Ok(ProgMode::Passthrough(None, vec)),
+        Some(vec) => Ok(ProgMode::Passthrough(Some(Box::new(mode)), vec)),
+        None => Ok(mode),
+    }
+}
+
+/// Builds the arguments of the command `name` from its parsed command line.
+fn command_mode(name: &str, args: ParsedArgs) -> Result<ProgMode> {
+    let mode = match name {
+        "add" => ProgMode::Add(AddArgs::new(args)?),
+        "rm" => ProgMode::Rm(RmArgs::new(args)?),
+        "diff" => ProgMode::Diff(DiffArgs::new(args)?),
+        "remote" => ProgMode::Remote(RemoteArgs::new(args)?),
+        "push" => ProgMode::Push(PushArgs::new(args)?),
+        "fetch" => ProgMode::Fetch(FetchArgs::new(args)?),
+        "pull" => ProgMode::Pull(PullArgs::new(args)?),
+        "clone" => match args.values.as_slice() {
+            [url] => ProgMode::Clone(url.clone()),
+            _ => return Err(usage_error("clone")),
+        },
+        "show" => ProgMode::Show(ShowArgs::new(args)?),
+        "restore" => ProgMode::Restore(RestoreArgs::new(args)?),
+        "reset" => ProgMode::Reset(ResetArgs::new(args)?),
+        "branch" => ProgMode::Branch(BranchArgs::new(args)?),
+        "switch" => ProgMode::Switch(SwitchArgs::new(args)?),
+        "profile" => match args.values.as_slice() {
+            [] => ProgMode::Profile(false),
+            [command] if command == "show" => ProgMode::Profile(false),
+            [command] if command == "list" => ProgMode::Profile(true),
+            _ => return Err(usage_error("profile")),
+        },
+        "template" => ProgMode::Template(TemplateArgs::new(args)?),
+        "secrets" => ProgMode::Secrets(SecretsArgs::new(args)?),
+        "ignore" => ProgMode::Ignore(IgnoreArgs::new(args)?),
+        "config" => ProgMode::Config(ConfigArgs::new(args)?),
+        "doctor" => match args.values.is_empty() {
+            true => ProgMode::Doctor,
+            false => return Err(usage_error("doctor")),
+        },
+        "completions" => ProgMode::Completions(CompletionsArgs::new(args)?),
+        "manpage" => match args.values.is_empty() {
+            true => ProgMode::Manpage,
+            false => return Err(usage_error("manpage")),
+        },
+        "init" => match args.values.is_empty() {
+            true => ProgMode::Init,
+            false => return Err(Error::Usage(String::from("home init takes no args."))),
+        },
+        "ls-files" => ProgMode::LsFiles(LsFilesArgs::new(args)?),
+        "status" => ProgMode::Status(StatusArgs::new(args)?),
+        "commit" => ProgMode::Commit(CommitArgs::new(args)?),
+        "log" => ProgMode::Log(LogArgs::new(args)?),
+        _ => return Err(usage_error(name)),
     };
 
-    if temp_mode == "add" {
-        mode = ProgMode::Add(AddArgs::new(prog_args.map(|x| String::from(x)).collect()));
-    } else if temp_mode == "init" {
-        mode = ProgMode::Init;
-        if let Some(_val) = prog_args.next() {
-            eprintln!("home init takes no args.");
-            exit(1);
-        }
-    } else if temp_mode == "status" {
-        let has_color = match env::var("COLORTERM") {
-            Ok(value) if value == "truecolor" || value == "24bit" => true,
-            _ => false,
-        };
-        mode = ProgMode::Status(has_color);
-    } else if temp_mode == "commit" {
-        mode = ProgMode::Commit(CommitArgs::new(prog_args.map(|x| String::from(x)).collect())?);
-    } else if temp_mode == "log" {
-        mode = ProgMode::Log;
-    } else if temp_mode == "--help" {
-        mode = ProgMode::Help;
-    }
-    if let Some(vec) = passthroughp {
-	if let ProgMode::None = mode {
-	    mode = ProgMode::Passthrough(None, vec)	    
-	} else {
-	    let prefix_mode = mode;
-	    mode = ProgMode::Passthrough(Some(Box::new(prefix_mode)), vec);
-	}
-
-    }
- Ok(mode)
+    Ok(mode)
 }
@@ -0,0 +1,284 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Splits a command's arguments into options and values.
+//!
+//! Options follow the GNU conventions: short flags can be combined (`-uf`),
+//! a short option's value can follow it directly (`-n5`) or as the next
+//! argument, and a long option's value is given as `--opt=value` or
+//! `--opt value`. `-h` and `--help` are accepted by every command.
+
+use git_home::{Error, Result};
+
+/// An option a command accepts.
+pub struct Opt {
+    /// The name the option is known by once parsed. Aliases share a name.
+    pub name: &'static str,
+    pub short: Option<char>,
+    pub long: Option<&'static str>,
+    /// What the value it takes is called, or None for a flag.
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+/// The option every command accepts.
+pub const HELP: Opt = Opt {
+    name: "help",
+    short: Some('h'),
+    long: Some("help"),
+    value: None,
+    help: "print this help.",
+};
+
+impl Opt {
+    /// How the option is written in help, such as `-n, --max-count <count>`.
+    pub fn synopsis(&self) -> String {
+        let mut synopsis = match (self.short, self.long) {
+            (Some(short), Some(long)) => format!("-{}, --{}", short, long),
+            (Some(short), None) => format!("-{}", short),
+            (None, Some(long)) => format!("--{}", long),
+            (None, None) => String::new(),
+        };
+        if let Some(value) = self.value {
+            synopsis.push_str(&format!(" <{}>", value));
+        }
+        synopsis
+    }
+}
+
+/// A command line after parsing.
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedArgs {
+    /// The options given, by name and in order, with their values.
+    pub options: Vec<(&'static str, Option<String>)>,
+    /// The arguments that are not options, in order.
+    pub values: Vec<String>,
+}
+
+impl ParsedArgs {
+    /// Returns true if the option `name` was given.
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(x, _)| *x == name)
+    }
+
+    /// Returns the value of the last `name` option given.
+    pub fn value(&self, name: &str) -> Option<String> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(x, _)| *x == name)
+            .and_then(|(_, value)| value.clone())
+    }
+
+    /// Returns the values of every `name` option given, in order.
+    pub fn all(&self, name: &str) -> Vec<String> {
+        self.options
+            .iter()
+            .filter(|(x, _)| *x == name)
+            .filter_map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+/// Parses the arguments of `git home <command>` against `options`.
+pub fn parse(command: &str, args: Vec<String>, options: &'static [Opt]) -> Result<ParsedArgs> {
+    let find_short = |c: char| {
+        options
+            .iter()
+            .chain([&HELP])
+            .find(|x| x.short == Some(c))
+            .ok_or_else(|| unknown_option(command, &format!("-{}", c)))
+    };
+    let find_long = |name: &str| {
+        options
+            .iter()
+            .chain([&HELP])
+            .find(|x| x.long == Some(name))
+            .ok_or_else(|| unknown_option(command, &format!("--{}", name)))
+    };
+
+    let mut parsed = ParsedArgs::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(String::from(value))),
+                None => (long, None),
+            };
+            let opt = find_long(name)?;
+            let value = match (opt.value, value) {
+                (None, None) => None,
+                (None, Some(_)) => {
+                    return Err(Error::Usage(format!("--{} does not take a value.", name)));
+                }
+                (Some(_), Some(value)) => Some(value),
+                (Some(_), None) => Some(
+                    args.next()
+                        .ok_or_else(|| requires_value(&format!("--{}", name)))?,
+                ),
+            };
+            parsed.options.push((opt.name, value));
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            for (i, c) in arg[1..].char_indices() {
+                let opt = find_short(c)?;
+                if opt.value.is_none() {
+                    parsed.options.push((opt.name, None));
+                    continue;
+                }
+                // The rest of the argument is the value, or the next one is.
+                let rest = &arg[1 + i + c.len_utf8()..];
+                let value = match rest.is_empty() {
+                    true => args
+                        .next()
+                        .ok_or_else(|| requires_value(&format!("-{}", c)))?,
+                    false => String::from(rest),
+                };
+                parsed.options.push((opt.name, Some(value)));
+                break;
+            }
+        } else {
+            parsed.values.push(arg);
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn unknown_option(command: &str, option: &str) -> Error {
+    Error::Usage(format!(
+        "Unknown option {} for git home {}.\nRun 'git home {} --help' to see what it takes.",
+        option, command, command
+    ))
+}
+
+fn requires_value(option: &str) -> Error {
+    Error::Usage(format!("{} requires a value.", option))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: &[Opt] = &[
+        Opt {
+            name: "update",
+            short: Some('u'),
+            long: Some("update"),
+            value: None,
+            help: "",
+        },
+        Opt {
+            name: "force",
+            short: Some('f'),
+            long: Some("force"),
+            value: None,
+            help: "",
+        },
+        Opt {
+            name: "count",
+            short: Some('n'),
+            long: Some("max-count"),
+            value: Some("count"),
+            help: "",
+        },
+    ];
+
+    fn parse_args(args: &[&str]) -> Result<ParsedArgs> {
+        parse(
+            "test",
+            args.iter().map(|x| String::from(*x)).collect(),
+            OPTIONS,
+        )
+    }
+
+    #[test]
+    fn combined_short_flags() {
+        let parsed = parse_args(&["-uf", "file"]).unwrap();
+        assert!(parsed.flag("update"));
+        assert!(parsed.flag("force"));
+        assert_eq!(parsed.values, vec!["file"]);
+    }
+
+    #[test]
+    fn short_option_values() {
+        assert_eq!(
+            parse_args(&["-n5"]).unwrap().value("count").as_deref(),
+            Some("5")
+        );
+        assert_eq!(
+            parse_args(&["-n", "5"]).unwrap().value("count").as_deref(),
+            Some("5")
+        );
+        assert_eq!(
+            parse_args(&["-un5"]).unwrap().value("count").as_deref(),
+            Some("5")
+        );
+    }
+
+    #[test]
+    fn long_option_values() {
+        assert_eq!(
+            parse_args(&["--max-count=5"])
+                .unwrap()
+                .value("count")
+                .as_deref(),
+            Some("5")
+        );
+        assert_eq!(
+            parse_args(&["--max-count", "5"])
+                .unwrap()
+                .value("count")
+                .as_deref(),
+            Some("5")
+        );
+    }
+
+    #[test]
+    fn last_value_wins() {
+        let parsed = parse_args(&["-n1", "--max-count=2"]).unwrap();
+        assert_eq!(parsed.value("count").as_deref(), Some("2"));
+        assert_eq!(parsed.all("count"), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn values_keep_their_order() {
+        let parsed = parse_args(&["a", "-u", "b", "-"]).unwrap();
+        assert_eq!(parsed.values, vec!["a", "b", "-"]);
+    }
+
+    #[test]
+    fn help_is_always_accepted() {
+        assert!(parse_args(&["-h"]).unwrap().flag("help"));
+        assert!(parse_args(&["--help"]).unwrap().flag("help"));
+    }
+
+    #[test]
+    fn unknown_options_are_usage_errors() {
+        assert!(matches!(parse_args(&["-x"]), Err(Error::Usage(_))));
+        assert!(matches!(parse_args(&["--unknown"]), Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn missing_values_are_usage_errors() {
+        assert!(matches!(parse_args(&["-n"]), Err(Error::Usage(_))));
+        assert!(matches!(parse_args(&["--max-count"]), Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn flags_do_not_take_values() {
+        assert!(matches!(parse_args(&["--force=yes"]), Err(Error::Usage(_))));
+    }
+}
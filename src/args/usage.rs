@@ -14,49 +14,153 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::git::resolve_git_repo;
-use crate::GIT_HOME_DIR;
+use super::parser::{Opt, HELP};
+use super::{find_command, COMMANDS};
+use crate::cli::load_config;
+use git_home::config::SETTINGS;
+use git_home::error::EXIT_CODES;
+use git_home::GIT_HOME_CONFIG_FILE;
 use std::io;
 
-pub fn print_commit_usage() {
-    println!("Usage: ");
-    println!("\tgit home commit <command>");
+/// Prints the usage and options of the command `name`.
+pub fn print_command_help(name: &str) -> io::Result<()> {
+    let command = match find_command(name) {
+        Some(command) => command,
+        None => return print_usage(),
+    };
+
+    for (i, form) in command.usage.iter().enumerate() {
+        let prefix = if i == 0 { "Usage:" } else { "   or:" };
+        println!(
+            "{}",
+            format!("{} git home {} {}", prefix, command.name, form).trim_end()
+        );
+    }
     println!();
-    println!("Options: ");
-    println!("\t[-m | --message=]\"message\":");
-    println!("\t\t Commits index to working head with message.")
+    println!("Options:");
+    let options: Vec<&Opt> = command.options.iter().chain([&HELP]).collect();
+    let width = options
+        .iter()
+        .map(|x| x.synopsis().len())
+        .max()
+        .unwrap_or_default();
+    for i in options {
+        println!("\t{:width$}  {}", i.synopsis(), i.help, width = width);
+    }
+
+    Ok(())
 }
 
-pub fn print_add_help() {
-    println!("git home add <file>");
+/// Prints the help of `command`, or the usage of git home without one.
+pub fn print_help(command: Option<&str>) -> io::Result<()> {
+    match command {
+        Some(name) => print_command_help(name),
+        None => print_usage(),
+    }
 }
 
-pub fn print_usage() -> io::Result<()> {
-    let git_dir = match resolve_git_repo() {
-        Ok(string) => string,
-        Err(_string) => format!("$HOME/{} (default value)", GIT_HOME_DIR),
-    };
+pub fn print_version() -> io::Result<()> {
+    println!("git-home {}", env!("CARGO_PKG_VERSION"));
+    Ok(())
+}
+
+/// What git home takes instead of a command, with what each does.
+pub const GLOBAL_OPTIONS: &[(&str, &str)] = &[
+    ("--help", "prints this help dialog. 'git home <command> --help' shows the options of a command."),
+    ("--version", "prints the version of git home."),
+    (
+        "--",
+        "passes any commands following the double dashes to git. Any command preceding the double dash will be executed first.",
+    ),
+];
+
+/// The environment variables read besides those overriding settings.
+pub const OTHER_VARIABLES: &[(&str, &str)] = &[
+    (
+        "GIT_HOME_PROFILE",
+        "selects a host profile by name instead of by hostname.",
+    ),
+    (
+        "GIT_HOME_PASSPHRASE",
+        "encrypts secrets with a passphrase instead of a key file.",
+    ),
+    (
+        "GIT_HOME_NEW_PASSPHRASE",
+        "the passphrase secrets rekey switches to instead of a new key file.",
+    ),
+];
 
+/// Splits `text` into lines of at most `width` characters, breaking at spaces.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split(' ') {
+        match lines.last_mut() {
+            Some(line) if line.len() + 1 + word.len() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(String::from(word)),
+        }
+    }
+    lines
+}
+
+/// Prints `name: text` in the commands list, wrapping the text under itself.
+fn print_entry(name: &str, text: &str) {
+    for (i, line) in wrap(text, 62).iter().enumerate() {
+        match i {
+            0 => println!("\t{:>7}: {}", name, line),
+            _ => println!("\t{:>7}  {}", "", line),
+        }
+    }
+}
+
+pub fn print_usage() -> io::Result<()> {
     println!("Usage:");
     println!("\tgit home [command] <args>");
     println!("Commands:");
-    println!("\t    add: add a file to the git_home repo.");
-    println!("\t status: print staus of files in the index.");
-    println!("\t   init: initialize a new home repo.");
-    println!("\t commit: commit current index to repository.");
-    println!("\t    log: prints a log of the last commit.");
-    println!("\t --help: prints this help dialog.");
-    println!("\t     --: passes any commands following the double dashes to git.");
-    println!("\t         any command preceding the double dash will be executed first.");
+    for command in COMMANDS {
+        print_entry(command.name, command.summary);
+    }
+    for (name, text) in GLOBAL_OPTIONS {
+        print_entry(name, text);
+    }
     println!();
     println!("\t\t For example, to commit your changes and then see a log of");
     println!("\t\t your commit history you could run:");
     println!();
     println!("\t\t\t git home commit -m \"some message\" -- status | less");
     println!();
-    
+
+    println!("Settings ($HOME/{}):", GIT_HOME_CONFIG_FILE);
+    let config = load_config().ok();
+    for setting in SETTINGS {
+        println!("\t{}: {}", setting.key, setting.description);
+        let value = config.as_ref().and_then(|config| config.get(setting.key));
+        match value {
+            Some((value, source)) => println!("\t\t{} ({})", value, source),
+            None => println!("\t\tnot set"),
+        }
+    }
+    println!();
+
     println!("Global Variables:");
-    println!("\tGIT_HOME_DIR: {}", git_dir);
+    for setting in SETTINGS {
+        println!(
+            "\t{}: overrides the {} setting.",
+            setting.env.join(", "),
+            setting.key
+        );
+    }
+    for (name, text) in OTHER_VARIABLES {
+        println!("\t{}: {}", name, text);
+    }
+    println!();
+
+    println!("Exit Codes:");
+    for (code, text) in EXIT_CODES {
+        println!("\t{}: {}", code, text);
+    }
 
     Ok(())
 }
@@ -0,0 +1,722 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Helpers shared by the commands: opening the home repo from the
+//! environment and printing what the library returns.
+
+use crate::args::{LsFilesArgs, StatusArgs, StatusFormat};
+use chrono::{Local, TimeZone};
+use git2::{Diff, DiffLine, DiffOptions, Oid, Patch, Repository};
+use git_home::config::Config;
+use git_home::doctor::{Check, Outcome};
+use git_home::git::{
+    current_branch, gen_branch_string, gen_home_contents, is_filtered, ChangeKind, Progress,
+};
+use git_home::profile::{get_hostname, resolve_profile};
+use git_home::secret::{is_encrypted, Key};
+use git_home::{Context, Environment, Error, GitHome, LogEntry, Result, Status, TrackedFile};
+use std::{collections::BTreeMap, env, fs, io, io::prelude::*, path::Path, process::Command};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Gets the absolute path of the git_home_directory from the repo setting.
+pub fn resolve_git_repo() -> Result<String> {
+    // The repo setting has a default, so it is always set.
+    Ok(load_config()?
+        .get_path("repo")
+        .map(|(path, _)| path)
+        .unwrap_or_default())
+}
+
+/// Loads the config file in $HOME.
+pub fn load_config() -> Result<Config> {
+    Config::load(get_home_dir()?)
+}
+
+/// Returns the value of $HOME.
+pub fn get_home_dir() -> Result<String> {
+    env::var("HOME").map_err(|_| Error::MissingEnv("HOME"))
+}
+
+/// Returns what the library takes in place of the environment, printing
+/// its warnings to stderr.
+pub fn get_environment() -> Environment {
+    Environment {
+        user: env::var("USER").unwrap_or_default(),
+        passphrase: env::var("GIT_HOME_PASSPHRASE").ok(),
+        new_passphrase: env::var("GIT_HOME_NEW_PASSPHRASE").ok(),
+        profile: env::var("GIT_HOME_PROFILE").ok(),
+        warn: |message| eprintln!("warning: {}", message),
+        ..Environment::default()
+    }
+}
+
+/// Prints the progress of a fetch or push the way git does.
+pub fn print_progress(progress: Progress) {
+    let (label, current, total) = match progress {
+        Progress::Remote(data) => {
+            eprint!("remote: {}", String::from_utf8_lossy(data));
+            return;
+        }
+        Progress::Receiving(current, total) => ("Receiving", current, total),
+        Progress::Writing(current, total) => ("Writing", current, total),
+    };
+    eprint!("\r{} objects: {}/{}", label, current, total);
+    if current == total {
+        eprintln!(", done.");
+    }
+}
+
+/// Returs the home repository
+pub fn open_home_repo() -> Result<Repository> {
+    Ok(open_git_home()?.into_repo())
+}
+
+/// Opens the home repo at GIT_HOME_DIR with $HOME as its work tree, offering
+/// to create it if it does not exist.
+pub fn open_git_home() -> Result<GitHome> {
+    let git_home_dir = resolve_git_repo()?;
+    let home_dir = get_home_dir()?;
+
+    let home = match GitHome::open(&git_home_dir, &home_dir, get_environment()) {
+        Ok(home) => home,
+        Err(_e) => {
+            println!(
+                "Git home repo doesn't exist, create one now at {}?",
+                git_home_dir
+            );
+            print!("(y/n) ");
+            io::stdout().flush()?;
+            let mut buffer = String::new();
+            io::stdin().read_line(&mut buffer)?;
+            buffer.pop();
+            if buffer == "y" {
+                println!("Creating git home repo: {}.", git_home_dir);
+                init_git_home(&git_home_dir, &home_dir).context(format!(
+                    "Could not initialize git_home directory at {}",
+                    git_home_dir
+                ))?
+            } else {
+                return Err(Error::NoRepo(git_home_dir));
+            }
+        }
+    };
+
+    warn_profile_branch(&home);
+    Ok(home)
+}
+
+/// Creates a new home repo, starting on the branch setting if it is set.
+pub fn init_git_home(git_dir: &str, home_dir: &str) -> Result<GitHome> {
+    let home = GitHome::init(git_dir, home_dir, get_environment())?;
+    if let Some((branch, _)) = load_config()?.get("branch") {
+        home.repo().set_head(&format!("refs/heads/{}", branch))?;
+    }
+    Ok(home)
+}
+
+/// Warns if the checked out branch is not the one the host profile expects.
+fn warn_profile_branch(home: &GitHome) {
+    let repo = home.repo();
+    let hostname = get_hostname();
+    if let Some((profile, _)) = resolve_profile(repo, &hostname, home.env()) {
+        let current = current_branch(repo);
+        if let Some(branch) = profile
+            .branch
+            .filter(|branch| Some(branch) != current.as_ref())
+        {
+            eprintln!(
+                "warning: profile '{}' for {} expects branch '{}', but {}.",
+                profile.name,
+                hostname,
+                branch,
+                gen_branch_string(repo).to_lowercase()
+            );
+        }
+    }
+}
+
+/// Prints the outcome of each doctor check, with the fix for any problem.
+pub fn print_checks(checks: &[Check], color: bool) {
+    let width = checks
+        .iter()
+        .map(|x| x.name.len())
+        .max()
+        .unwrap_or_default();
+    for i in checks {
+        let (label, label_color) = match i.outcome {
+            Outcome::Pass => ("pass", GREEN),
+            Outcome::Warn => ("warn", YELLOW),
+            Outcome::Fail => ("fail", RED),
+        };
+        match color {
+            true => print!("{label_color}{label}{RESET}"),
+            false => print!("{label}"),
+        }
+        println!("  {:width$}  {}", i.name, i.message, width = width);
+        if let Some(fix) = &i.fix {
+            println!("      {:width$}  {}", "", fix, width = width);
+        }
+    }
+}
+
+/// Prints the satus of the home repo.
+pub fn print_repo_status(args: StatusArgs) -> Result<()> {
+    let home = open_git_home()?;
+    let status = home.status()?;
+    let untracked = match args.untracked {
+        true => home.untracked()?,
+        false => Vec::new(),
+    };
+    match args.format {
+        StatusFormat::Porcelain => {
+            let escape_non_ascii = home
+                .repo()
+                .config()
+                .and_then(|config| config.get_bool("core.quotePath"))
+                .unwrap_or(true);
+            return Ok(print_porcelain_status(
+                status,
+                &untracked,
+                &args,
+                escape_non_ascii,
+            )?);
+        }
+        StatusFormat::Json => return Ok(print_json_status(status, &untracked, &args)?),
+        StatusFormat::Human => (),
+    }
+    let mut up_to_date = true;
+    println!("{}", gen_branch_string(home.repo()));
+    if !status.unstaged.is_empty() {
+        up_to_date = false;
+        if args.color {
+            print!("{RED}");
+        }
+        println!("Files with untracked changes:\n\tYou can run 'git home add -u' to add them to the index'");
+        for i in status.unstaged.iter() {
+            println!("\t{}", i.describe());
+        }
+        if args.color {
+            print!("{RESET}");
+        }
+    }
+
+    if !status.staged.is_empty() {
+        up_to_date = false;
+        if args.color {
+            print!("{GREEN}");
+        }
+        println!("Files with changes to be commited:");
+        for i in status.staged.iter() {
+            println!("\t{}", i.describe());
+        }
+        if args.color {
+            print!("{RESET}");
+        }
+    }
+
+    if !untracked.is_empty() {
+        if args.color {
+            print!("{RED}");
+        }
+        println!("Untracked files next to tracked ones:");
+        for i in untracked.iter() {
+            println!("\t$HOME/{}", i);
+        }
+        if args.color {
+            print!("{RESET}");
+        }
+    }
+
+    if up_to_date {
+        println!("Everything is upto date.");
+    }
+    Ok(())
+}
+
+/// A tracked file with its change in the index and in $HOME.
+struct FileState {
+    path: String,
+    old_path: Option<String>,
+    index: Option<ChangeKind>,
+    workdir: Option<ChangeKind>,
+}
+
+/// Merges the index and $HOME changes of every changed file, sorted by path.
+fn get_file_states(status: Status) -> Vec<FileState> {
+    let mut states: Vec<FileState> = status
+        .staged
+        .into_iter()
+        .map(|x| FileState {
+            path: x.path,
+            old_path: x.old_path,
+            index: Some(x.kind),
+            workdir: None,
+        })
+        .collect();
+    for i in status.unstaged {
+        match states.iter_mut().find(|x| x.path == i.path) {
+            Some(state) => state.workdir = Some(i.kind),
+            None => states.push(FileState {
+                path: i.path,
+                old_path: i.old_path,
+                index: None,
+                workdir: Some(i.kind),
+            }),
+        }
+    }
+    states.sort_by(|a, b| a.path.cmp(&b.path));
+    states
+}
+
+/// Quotes `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut output = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// Quotes `path` as `git status --porcelain` does if it holds a space, a
+/// quote, a backslash or a control character, escaping all but the space.
+/// With `escape_non_ascii`, git's core.quotePath, bytes outside ASCII are
+/// escaped too.
+fn quote_path(path: &str, escape_non_ascii: bool) -> String {
+    let mut quoted = Vec::new();
+    let mut needs_quotes = false;
+    for byte in path.bytes() {
+        let escape = match byte {
+            b'"' => "\\\"",
+            b'\\' => "\\\\",
+            0x07 => "\\a",
+            0x08 => "\\b",
+            b'\t' => "\\t",
+            b'\n' => "\\n",
+            0x0b => "\\v",
+            0x0c => "\\f",
+            b'\r' => "\\r",
+            byte if byte < 0x20 || byte == 0x7f || (byte >= 0x80 && escape_non_ascii) => {
+                quoted.extend(format!("\\{:03o}", byte).bytes());
+                needs_quotes = true;
+                continue;
+            }
+            byte => {
+                needs_quotes |= byte == b' ';
+                quoted.push(byte);
+                continue;
+            }
+        };
+        quoted.extend(escape.bytes());
+        needs_quotes = true;
+    }
+    match needs_quotes {
+        true => format!("\"{}\"", String::from_utf8_lossy(&quoted)),
+        false => String::from(path),
+    }
+}
+
+/// Prints the status in a stable format for scripts, modeled on
+/// `git status --porcelain --branch`. Paths are relative to $HOME and quoted
+/// as git does, unless `args.null_terminated` is set.
+fn print_porcelain_status(
+    status: Status,
+    untracked: &[String],
+    args: &StatusArgs,
+    escape_non_ascii: bool,
+) -> io::Result<()> {
+    let end = if args.null_terminated { '\0' } else { '\n' };
+    let quote = |path: &str| match args.null_terminated {
+        true => String::from(path),
+        false => quote_path(path, escape_non_ascii),
+    };
+    let mut output = String::from("## ");
+    match &status.branch {
+        Some(branch) => output.push_str(branch),
+        None => output.push_str("HEAD (no branch)"),
+    }
+    if let Some(upstream) = &status.upstream {
+        output.push_str(&format!("...{}", upstream.name));
+        match (upstream.ahead, upstream.behind) {
+            (0, 0) => (),
+            (ahead, 0) => output.push_str(&format!(" [ahead {}]", ahead)),
+            (0, behind) => output.push_str(&format!(" [behind {}]", behind)),
+            (ahead, behind) => output.push_str(&format!(" [ahead {}, behind {}]", ahead, behind)),
+        }
+    }
+    output.push(end);
+
+    for i in get_file_states(status) {
+        let index = i.index.map_or(' ', |x| x.code());
+        let workdir = i.workdir.map_or(' ', |x| x.code());
+        match (&i.old_path, args.null_terminated) {
+            // As in git, -z puts the new path first and skips the arrow.
+            (Some(old_path), true) => {
+                output.push_str(&format!("{index}{workdir} {}\0{}\0", i.path, old_path))
+            }
+            (Some(old_path), false) => output.push_str(&format!(
+                "{index}{workdir} {} -> {}\n",
+                quote(old_path),
+                quote(&i.path)
+            )),
+            (None, _) => output.push_str(&format!("{index}{workdir} {}{end}", quote(&i.path))),
+        }
+    }
+    for i in untracked {
+        output.push_str(&format!("?? {}{end}", quote(i)));
+    }
+
+    let mut stdout = io::stdout();
+    stdout.write_all(output.as_bytes())?;
+    stdout.flush()
+}
+
+/// A directory, or a file, in the tree `ls-files --tree` prints.
+#[derive(Default)]
+struct FileTree<'a> {
+    file: Option<&'a TrackedFile>,
+    children: BTreeMap<&'a str, FileTree<'a>>,
+}
+
+fn gen_file_tree(files: &[TrackedFile]) -> FileTree<'_> {
+    let mut root = FileTree::default();
+    for i in files {
+        let mut node = &mut root;
+        for part in i.path.split('/') {
+            node = node.children.entry(part).or_default();
+        }
+        node.file = Some(i);
+    }
+    root
+}
+
+/// The mode and size columns of `ls-files -l`, blank for a directory.
+fn gen_file_columns(file: Option<&TrackedFile>) -> String {
+    match file {
+        Some(file) => format!("{:06o} {:>8}  ", file.mode, file.size),
+        None => format!("{:6} {:>8}  ", "", ""),
+    }
+}
+
+fn push_file_tree(output: &mut String, node: &FileTree, prefix: &str, long: bool) {
+    let count = node.children.len();
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let last = i + 1 == count;
+        if long {
+            output.push_str(&gen_file_columns(child.file));
+        }
+        let branch = if last { "└── " } else { "├── " };
+        output.push_str(&format!("{prefix}{branch}{name}\n"));
+        let indent = if last { "    " } else { "│   " };
+        push_file_tree(output, child, &format!("{prefix}{indent}"), long);
+    }
+}
+
+/// Prints the files listed by `ls-files`, as a list or a tree.
+pub fn print_tracked_files(files: &[TrackedFile], args: &LsFilesArgs) -> io::Result<()> {
+    let mut output = String::new();
+    if args.tree {
+        if args.long {
+            output.push_str(&gen_file_columns(None));
+        }
+        output.push_str("$HOME\n");
+        push_file_tree(&mut output, &gen_file_tree(files), "", args.long);
+    } else {
+        // Scripts get paths relative to $HOME, as with status --porcelain.
+        let (prefix, end) = match args.null_terminated {
+            true => ("", '\0'),
+            false => ("$HOME/", '\n'),
+        };
+        for i in files {
+            if args.long {
+                output.push_str(&gen_file_columns(Some(i)));
+            }
+            output.push_str(&format!("{prefix}{}{end}", i.path));
+        }
+    }
+    let mut stdout = io::stdout();
+    stdout.write_all(output.as_bytes())?;
+    stdout.flush()
+}
+
+/// Prints the status as a JSON object.
+fn print_json_status(status: Status, untracked: &[String], args: &StatusArgs) -> io::Result<()> {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| String::from("null"));
+    let upstream = &status.upstream;
+    let mut fields = vec![
+        format!(
+            "\"branch\":{}",
+            optional(status.branch.as_deref().map(json_string))
+        ),
+        format!(
+            "\"upstream\":{}",
+            optional(upstream.as_ref().map(|x| json_string(&x.name)))
+        ),
+        format!(
+            "\"ahead\":{}",
+            optional(upstream.as_ref().map(|x| x.ahead.to_string()))
+        ),
+        format!(
+            "\"behind\":{}",
+            optional(upstream.as_ref().map(|x| x.behind.to_string()))
+        ),
+    ];
+
+    let files: Vec<String> = get_file_states(status)
+        .into_iter()
+        .map(|i| {
+            format!(
+                "{{\"path\":{},\"old_path\":{},\"index\":{},\"workdir\":{}}}",
+                json_string(&i.path),
+                optional(i.old_path.as_deref().map(json_string)),
+                optional(i.index.map(|x| json_string(x.name()))),
+                optional(i.workdir.map(|x| json_string(x.name()))),
+            )
+        })
+        .collect();
+    fields.push(format!("\"files\":[{}]", files.join(",")));
+    if args.untracked {
+        let untracked: Vec<String> = untracked.iter().map(|x| json_string(x)).collect();
+        fields.push(format!("\"untracked\":[{}]", untracked.join(",")));
+    }
+
+    println!("{{{}}}", fields.join(","));
+    Ok(())
+}
+
+fn print_diff_line(stdout: &mut io::Stdout, line: &DiffLine, has_color: bool) -> io::Result<()> {
+    let (color, origin) = match line.origin() {
+        '+' => (GREEN, "+"),
+        '-' => (RED, "-"),
+        ' ' => ("", " "),
+        'F' => (BOLD, ""),
+        'H' => (CYAN, ""),
+        _ => ("", ""),
+    };
+    let content = String::from_utf8_lossy(line.content());
+    if has_color && !color.is_empty() {
+        writeln!(
+            stdout,
+            "{color}{origin}{}{RESET}",
+            content.trim_end_matches('\n')
+        )
+    } else {
+        write!(stdout, "{origin}{content}")
+    }
+}
+
+/// Prints `diff` as a patch, in color if `has_color` is set.
+///
+/// Secrets are decrypted so their changes can be read. When `workdir` is set
+/// the new side of the diff is $HOME, and templates are rendered to compare
+/// like with like. Files that only differ before that are left out.
+pub fn print_diff(repo: &Repository, diff: &Diff, workdir: bool, has_color: bool) -> Result<()> {
+    let home_dir = repo.workdir().unwrap_or_else(|| Path::new(""));
+    let env = get_environment();
+    let mut options = DiffOptions::new();
+    options.old_prefix("$HOME/").new_prefix("$HOME/");
+
+    let mut stdout = io::stdout();
+    for (i, delta) in diff.deltas().enumerate() {
+        let path = match delta.new_file().path().or_else(|| delta.old_file().path()) {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => continue,
+        };
+        // Checking the blobs as well catches files that stopped being secrets.
+        let encrypted = [delta.old_file().id(), delta.new_file().id()]
+            .into_iter()
+            .any(|id| {
+                repo.find_blob(id)
+                    .is_ok_and(|blob| is_encrypted(blob.content()))
+            });
+        let buffers = if encrypted || is_filtered(home_dir, &path) {
+            let read_blob = |id: Oid| -> Vec<u8> {
+                let blob = match repo.find_blob(id) {
+                    Ok(blob) => blob.content().to_vec(),
+                    Err(_) => return Vec::new(),
+                };
+                match (workdir, is_encrypted(&blob)) {
+                    (true, _) => gen_home_contents(home_dir, &path, &blob, &env),
+                    (false, true) => Key::load(home_dir, &env).and_then(|key| key.decrypt(&blob)),
+                    (false, false) => None,
+                }
+                .unwrap_or(blob)
+            };
+            let old = read_blob(delta.old_file().id());
+            let new = match workdir {
+                true => fs::read(home_dir.join(&path)).unwrap_or_default(),
+                false => read_blob(delta.new_file().id()),
+            };
+            Some((old, new))
+        } else {
+            None
+        };
+        let patch = match &buffers {
+            Some((old, new)) => {
+                let path = Some(Path::new(&path));
+                Patch::from_buffers(old, path, new, path, Some(&mut options))
+                    .map(|patch| Some(patch).filter(|patch| patch.num_hunks() > 0))
+            }
+            None => Patch::from_diff(diff, i),
+        };
+        let mut write_err = None;
+        let result = match patch {
+            Ok(Some(mut patch)) => patch.print(&mut |_delta, _hunk, line| match print_diff_line(
+                &mut stdout,
+                &line,
+                has_color,
+            ) {
+                Ok(()) => true,
+                Err(e) => {
+                    write_err = Some(e);
+                    false
+                }
+            }),
+            Ok(None) => continue,
+            Err(e) => Err(e),
+        };
+        if let Some(e) = write_err {
+            return Err(e.into());
+        }
+        result.context("Could not print diff")?;
+    }
+    Ok(stdout.flush()?)
+}
+
+fn gen_repo_string() -> Result<String> {
+    let home = open_git_home()?;
+    let mut output = format!("# {}\n#\n", gen_branch_string(home.repo()));
+
+    let status = home.status()?;
+    if !status.unstaged.is_empty() {
+        output.push_str("# Files with untracked changes:\n#\tYou can run 'git home add -u' to add them to the index:\n");
+        for i in status.unstaged.iter() {
+            output.push_str(&format!("#\t{}\n", i.describe()));
+        }
+    }
+
+    if !status.staged.is_empty() {
+        output.push_str("# Files with changes to be commited:\n");
+        for i in status.staged.iter() {
+            output.push_str(&format!("#\t{}\n", i.describe()));
+        }
+    }
+
+    Ok(output)
+}
+
+/// Returns the header `git home log` and `git home show` print for a commit.
+pub fn gen_commit_header(commit: &LogEntry) -> String {
+    let sha = commit.id;
+    let author = format!("{} <{}>", commit.author, commit.email);
+    let time = match Local.timestamp_opt(commit.time, 0).single() {
+        Some(time) => time.to_string(),
+        None => commit.time.to_string(),
+    };
+    let mut output = format!("commit {sha}\nAuthor: {author}\nDate: {time}\n\n");
+    for line in commit.message.trim_end().lines() {
+        output.push_str(&format!("   {line}\n"));
+    }
+    output.push('\n');
+    output
+}
+
+#[allow(rustdoc::invalid_rust_codeblocks)]
+/**
+```no_run
+Please enter the commit message for your changes. Lines starting
+with '#' will be ignored, and an empty message aborts the commit.
+
+On branch main
+Your branch is up to date with 'origin/main'.
+
+Changes to be committed:
+modified:   ../../Cargo.lock
+modified:   ../../Cargo.toml
+modified:   mod.rs
+modified:   usage.rs
+modified:   ../git.rs
+modified:   ../main.rs
+modified:   ../run.rs
+modified:   ../../todo.org
+
+```
+
+ **/
+pub fn gen_commit_template() -> Result<String> {
+    let mut output = match load_config()?.get_path("commit-template") {
+        Some((path, _)) => {
+            let template = fs::read_to_string(&path)
+                .context(format!("Could not read commit template {}", path))?;
+            format!("{}\n", template.trim_end())
+        }
+        None => String::new(),
+    };
+    output.push_str(
+        "
+# Please enter the commit message for your changes. Lines starting
+# with '#' will be ignored, and an empty message aborts the commit.
+#\n",
+    );
+    let extension = gen_repo_string().context("unable to generate repo status info")?;
+    output.push_str(&extension);
+    Ok(output)
+}
+
+pub fn strip_commit_template(string: String) -> String {
+    let lines: Vec<&str> = string.lines().filter(|x| !x.starts_with('#')).collect();
+    String::from(lines.join("\n").trim())
+}
+
+/// Opens `text` in the editor setting and returns what was saved. Without
+/// one the editor is picked like git does.
+pub fn edit_message(text: String) -> Result<String> {
+    let editor = match load_config()?.get("editor") {
+        Some((editor, _)) => editor,
+        None => return Ok(edit::edit(text)?),
+    };
+    let mut file = edit::Builder::new()
+        .prefix("COMMIT_EDITMSG")
+        .tempfile()
+        .context("Could not create a file for the commit message")?;
+    file.write_all(text.as_bytes())?;
+    // Run through the shell so the setting can include arguments.
+    let status = Command::new("sh")
+        .args(["-c", &format!("{} \"$1\"", editor), "sh"])
+        .arg(file.path())
+        .status()
+        .context(format!("Could not run {}", editor))?;
+    if !status.success() {
+        return Err(Error::Refused(format!(
+            "{} exited with an error, commit aborted.",
+            editor
+        )));
+    }
+    Ok(fs::read_to_string(file.path())?)
+}
@@ -0,0 +1,305 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! User settings.
+//!
+//! Settings live in `$HOME/.config/git-home/config.toml`:
+//!
+//! ```text
+//! repo = "~/.config/git_home"
+//! remote = "origin"
+//! color = "auto"
+//! pager = "less"
+//! editor = "vim"
+//! commit-template = "~/.config/git-home/commit-template"
+//! branch = "main"
+//! ```
+//!
+//! Every setting can be overridden with an environment variable, so the
+//! effective value comes from the environment, then the file, then the
+//! built-in default.
+
+use super::GIT_HOME_CONFIG_FILE;
+use crate::error::{Context, Error, Result};
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A setting that can be given in the config file.
+pub struct Setting {
+    pub key: &'static str,
+    /// The environment variables overriding the file, checked in order.
+    pub env: &'static [&'static str],
+    pub default: Option<&'static str>,
+    /// The values it accepts, or empty if it accepts any.
+    pub values: &'static [&'static str],
+    pub description: &'static str,
+}
+
+/// Every setting, in the order they are listed.
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "repo",
+        env: &["GIT_HOME_DIR"],
+        // GIT_HOME_DIR under $HOME.
+        default: Some("~/.config/git_home"),
+        values: &[],
+        description: "where the home repo is kept.",
+    },
+    Setting {
+        key: "remote",
+        env: &["GIT_HOME_REMOTE"],
+        default: Some("origin"),
+        values: &[],
+        description: "the remote used when a branch has no upstream.",
+    },
+    Setting {
+        key: "color",
+        env: &["GIT_HOME_COLOR"],
+        default: Some("auto"),
+        values: &["auto", "always", "never"],
+        description: "whether status and diffs are colored.",
+    },
+    Setting {
+        key: "pager",
+        env: &["GIT_HOME_PAGER", "PAGER"],
+        default: Some("less"),
+        values: &[],
+        description: "the program long output is shown with.",
+    },
+    Setting {
+        key: "editor",
+        env: &["GIT_HOME_EDITOR", "VISUAL", "EDITOR"],
+        default: None,
+        values: &[],
+        description: "the program commit messages are written in.",
+    },
+    Setting {
+        key: "commit-template",
+        env: &["GIT_HOME_COMMIT_TEMPLATE"],
+        default: None,
+        values: &[],
+        description: "a file whose contents start every commit message.",
+    },
+    Setting {
+        key: "branch",
+        env: &["GIT_HOME_BRANCH"],
+        default: None,
+        values: &[],
+        description: "the first branch of a new home repo.",
+    },
+];
+
+/// Where the effective value of a setting came from.
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    /// Set with this environment variable.
+    Env(&'static str),
+    /// Set in the config file.
+    File,
+    /// Not set, so the built-in default is used.
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Env(name) => write!(f, "set by ${}", name),
+            Source::File => write!(f, "set in $HOME/{}", GIT_HOME_CONFIG_FILE),
+            Source::Default => write!(f, "default value"),
+        }
+    }
+}
+
+/// Looks up the setting called `key`.
+pub fn find_setting(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|x| x.key == key)
+}
+
+/// The settings in the config file, with the environment layered on top.
+pub struct Config {
+    home_dir: PathBuf,
+    table: toml::Table,
+}
+
+impl Config {
+    /// Reads the config file in `home_dir`. A missing file has no settings.
+    pub fn load(home_dir: impl AsRef<Path>) -> Result<Config> {
+        let home_dir = home_dir.as_ref().to_path_buf();
+        let table = match fs::read_to_string(home_dir.join(GIT_HOME_CONFIG_FILE)) {
+            Ok(text) => text
+                .parse::<toml::Table>()
+                .map_err(|e| Error::BadConfig(e.message().to_string()))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => toml::Table::new(),
+            Err(e) => {
+                return Err(e).context(format!("Could not read $HOME/{}", GIT_HOME_CONFIG_FILE))
+            }
+        };
+        Ok(Config { home_dir, table })
+    }
+
+    /// The path of the config file.
+    pub fn path(&self) -> PathBuf {
+        self.home_dir.join(GIT_HOME_CONFIG_FILE)
+    }
+
+    /// Returns the effective value of `key` and where it came from, or None
+    /// if it is not set and has no default.
+    pub fn get(&self, key: &str) -> Option<(String, Source)> {
+        let setting = find_setting(key)?;
+        for name in setting.env {
+            if let Ok(value) = env::var(name) {
+                if !value.is_empty() {
+                    return Some((value, Source::Env(name)));
+                }
+            }
+        }
+        if let Some(value) = self.table.get(key) {
+            let value = match value.as_str() {
+                Some(value) => String::from(value),
+                None => value.to_string(),
+            };
+            return Some((value, Source::File));
+        }
+        setting
+            .default
+            .map(|value| (String::from(value), Source::Default))
+    }
+
+    /// Like [`Config::get`], expanding a leading `~/` to the home directory.
+    pub fn get_path(&self, key: &str) -> Option<(String, Source)> {
+        let (value, source) = self.get(key)?;
+        match value.strip_prefix("~/") {
+            Some(rest) => Some((
+                self.home_dir.join(rest).to_string_lossy().into_owned(),
+                source,
+            )),
+            None => Some((value, source)),
+        }
+    }
+
+    /// Sets `key` in the config file. Call [`Config::save`] to write it.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let setting = match find_setting(key) {
+            Some(setting) => setting,
+            None => {
+                return Err(Error::NotFound(format!(
+                    "There is no setting called {}.",
+                    key
+                )))
+            }
+        };
+        if !setting.values.is_empty() && !setting.values.contains(&value) {
+            return Err(Error::Usage(format!(
+                "{} must be one of: {}",
+                key,
+                setting.values.join(", ")
+            )));
+        }
+        self.table
+            .insert(String::from(key), toml::Value::String(String::from(value)));
+        Ok(())
+    }
+
+    /// Writes the settings back to the config file, creating it if needed.
+    pub fn save(&self) -> Result<()> {
+        let path = self.path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context(format!("Could not create {}", parent.display()))?;
+        }
+        let text = toml::to_string(&self.table).map_err(|e| Error::BadConfig(e.to_string()))?;
+        fs::write(&path, text).context(format!("Could not write $HOME/{}", GIT_HOME_CONFIG_FILE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(text: &str) -> (tempfile::TempDir, Config) {
+        let home = tempfile::tempdir().unwrap();
+        let path = home.path().join(GIT_HOME_CONFIG_FILE);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, text).unwrap();
+        let config = Config::load(home.path()).unwrap();
+        (home, config)
+    }
+
+    #[test]
+    fn file_overrides_default() {
+        let (_home, config) = config("commit-template = \"~/template\"\n");
+        assert_eq!(config.get("branch"), None);
+        let (path, source) = config.get_path("commit-template").unwrap();
+        assert_eq!(source, Source::File);
+        assert!(path.ends_with("/template") && !path.starts_with('~'));
+    }
+
+    #[test]
+    fn missing_file_has_no_settings() {
+        let home = tempfile::tempdir().unwrap();
+        let config = Config::load(home.path()).unwrap();
+        assert!(config.table.is_empty());
+    }
+
+    // Environment variables are shared by every test in the process, so the
+    // ones this test sets are only read here.
+    #[test]
+    fn environment_overrides_file_and_default() {
+        env::remove_var("GIT_HOME_PAGER");
+        env::remove_var("PAGER");
+        let (_home, empty) = config("");
+        let (_home, config) = config("pager = \"more\"\n");
+        assert_eq!(
+            empty.get("pager"),
+            Some((String::from("less"), Source::Default))
+        );
+        assert_eq!(
+            config.get("pager"),
+            Some((String::from("more"), Source::File))
+        );
+
+        env::set_var("PAGER", "most");
+        assert_eq!(
+            config.get("pager"),
+            Some((String::from("most"), Source::Env("PAGER")))
+        );
+        env::set_var("GIT_HOME_PAGER", "cat");
+        assert_eq!(
+            config.get("pager"),
+            Some((String::from("cat"), Source::Env("GIT_HOME_PAGER")))
+        );
+        env::set_var("GIT_HOME_PAGER", "");
+        assert_eq!(
+            config.get("pager"),
+            Some((String::from("most"), Source::Env("PAGER")))
+        );
+        env::remove_var("GIT_HOME_PAGER");
+        env::remove_var("PAGER");
+    }
+
+    #[test]
+    fn rejects_unknown_settings_and_values() {
+        let (_home, mut config) = config("");
+        assert!(matches!(config.set("nope", "x"), Err(Error::NotFound(_))));
+        assert!(matches!(config.set("color", "x"), Err(Error::Usage(_))));
+        config.set("color", "never").unwrap();
+        assert_eq!(
+            config.get("color"),
+            Some((String::from("never"), Source::File))
+        );
+    }
+}
@@ -0,0 +1,432 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Checks for a broken or misconfigured home repo.
+//!
+//! [`diagnose`] looks at the environment, the config file and the home repo
+//! and returns a [`Check`] for each thing it looked at, with a suggested fix
+//! for anything that is wrong. Nothing is changed.
+
+use super::GIT_HOME_CONFIG_FILE;
+use crate::config::Config;
+use git2::{Repository, RepositoryState};
+use std::path::{Path, PathBuf};
+
+/// How a check went.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Outcome {
+    Pass,
+    /// git home works, but something is likely to cause trouble.
+    Warn,
+    /// git home will fail until this is fixed.
+    Fail,
+}
+
+/// The result of one check.
+#[derive(Debug)]
+pub struct Check {
+    /// What was checked, such as `signature`.
+    pub name: &'static str,
+    pub outcome: Outcome,
+    /// What was found.
+    pub message: String,
+    /// How to fix it, unless it passed.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl Into<String>) -> Check {
+        Check {
+            name,
+            outcome: Outcome::Pass,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Check {
+        Check {
+            name,
+            outcome: Outcome::Warn,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Check {
+        Check {
+            name,
+            outcome: Outcome::Fail,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Runs every check against the home repo of `home_dir`, which is None when
+/// $HOME is not set. Checks that depend on a failed one are skipped.
+pub fn diagnose(home_dir: Option<&Path>) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let home_dir = match home_dir {
+        Some(home_dir) if home_dir.is_dir() => {
+            checks.push(Check::pass("home", home_dir.display().to_string()));
+            home_dir
+        }
+        Some(home_dir) => {
+            checks.push(Check::fail(
+                "home",
+                format!("$HOME is {}, which is not a directory.", home_dir.display()),
+                "Set $HOME to your home directory.",
+            ));
+            return checks;
+        }
+        None => {
+            checks.push(Check::fail(
+                "home",
+                "$HOME is not set.",
+                "Set $HOME to your home directory.",
+            ));
+            return checks;
+        }
+    };
+
+    let config = match Config::load(home_dir) {
+        Ok(config) => {
+            checks.push(Check::pass("config", config.path().display().to_string()));
+            config
+        }
+        Err(e) => {
+            checks.push(Check::fail(
+                "config",
+                e.to_string(),
+                format!("Fix or remove $HOME/{}.", GIT_HOME_CONFIG_FILE),
+            ));
+            return checks;
+        }
+    };
+
+    // The repo setting has a default, so it is always set.
+    let (git_dir, source) = match config.get_path("repo") {
+        Some(repo) => repo,
+        None => return checks,
+    };
+    let repo = match check_repo(&git_dir, &source.to_string()) {
+        Ok((repo, check)) => {
+            checks.push(check);
+            repo
+        }
+        Err(check) => {
+            checks.push(check);
+            return checks;
+        }
+    };
+
+    checks.push(check_bare(&repo, &git_dir));
+    checks.push(check_workdir(&repo, home_dir));
+    checks.push(check_signature(&repo));
+    checks.push(check_head(&repo));
+    checks.push(check_locks(&repo));
+    checks.extend(check_remotes(&repo, home_dir));
+    checks.push(check_untracked_setting(&repo));
+    checks.push(check_integrity(&repo));
+    checks
+}
+
+/// Opens the repo at `git_dir`, which was set as described by `source`.
+fn check_repo(git_dir: &str, source: &str) -> Result<(Repository, Check), Check> {
+    let fix =
+        "Run 'git home init' or 'git home clone <url>', or set repo to where your home repo is.";
+    if let Ok(repo) = Repository::open_bare(git_dir) {
+        return Ok((
+            repo,
+            Check::pass("repo", format!("{} ({})", git_dir, source)),
+        ));
+    }
+    if Repository::open(git_dir).is_ok() {
+        return Err(Check::fail(
+            "repo",
+            format!(
+                "{} ({}) is a repo with its own work tree, not a home repo.",
+                git_dir, source
+            ),
+            format!(
+                "Make a home repo from it with 'git clone --bare {} <dir>' and set repo to <dir>.",
+                git_dir
+            ),
+        ));
+    }
+    let message = match Path::new(git_dir).exists() {
+        true => format!("{} ({}) is not a git repo.", git_dir, source),
+        false => format!("there is no home repo at {} ({}).", git_dir, source),
+    };
+    Err(Check::fail("repo", message, fix))
+}
+
+fn check_bare(repo: &Repository, git_dir: &str) -> Check {
+    let bare = repo
+        .config()
+        .and_then(|config| config.get_bool("core.bare"))
+        .unwrap_or(true);
+    match bare {
+        true => Check::pass("bare", "the repo is bare."),
+        false => Check::warn(
+            "bare",
+            "core.bare is false, so plain git treats the repo directory as a work tree.",
+            format!("Run 'git --git-dir {} config core.bare true'.", git_dir),
+        ),
+    }
+}
+
+/// git home always uses $HOME as the work tree, but plain git and other
+/// tools follow core.worktree.
+fn check_workdir(repo: &Repository, home_dir: &Path) -> Check {
+    let worktree = repo
+        .config()
+        .and_then(|config| config.get_path("core.worktree"));
+    match worktree {
+        Err(_) => Check::pass("workdir", home_dir.display().to_string()),
+        Ok(worktree) if same_dir(&worktree, home_dir) => {
+            Check::pass("workdir", home_dir.display().to_string())
+        }
+        Ok(worktree) => Check::warn(
+            "workdir",
+            format!("core.worktree is {}, not $HOME.", worktree.display()),
+            "Run 'git home -- config --unset core.worktree'.",
+        ),
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn check_signature(repo: &Repository) -> Check {
+    match repo.signature() {
+        Ok(signature) => Check::pass(
+            "signature",
+            format!("{} <{}>", signature.name().unwrap_or_default(), signature.email().unwrap_or_default()),
+        ),
+        Err(_) => Check::fail(
+            "signature",
+            "user.name or user.email is not set, so nothing can be committed.",
+            "Run 'git config --global user.name <name>' and 'git config --global user.email <email>'.",
+        ),
+    }
+}
+
+fn check_head(repo: &Repository) -> Check {
+    let operation = match repo.state() {
+        RepositoryState::Clean => None,
+        RepositoryState::Merge => Some("merge"),
+        RepositoryState::Revert | RepositoryState::RevertSequence => Some("revert"),
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => Some("cherry-pick"),
+        RepositoryState::Bisect => Some("bisect"),
+        _ => Some("rebase"),
+    };
+    if let Some(operation) = operation {
+        return Check::fail(
+            "head",
+            format!("a {} is in progress.", operation),
+            format!("Finish it, or run 'git home -- {} --abort'.", operation),
+        );
+    }
+
+    if repo.head_detached().unwrap_or(false) {
+        return Check::warn(
+            "head",
+            "HEAD is detached.",
+            "Run 'git home switch <branch>'.",
+        );
+    }
+    match repo.head() {
+        Ok(head) => Check::pass(
+            "head",
+            format!("on branch {}.", head.shorthand().unwrap_or_default()),
+        ),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => Check::warn(
+            "head",
+            "there are no commits yet.",
+            "Add some files with 'git home add' and run 'git home commit'.",
+        ),
+        Err(e) => Check::fail(
+            "head",
+            format!("HEAD can not be read: {}", e.message()),
+            "Run 'git home -- fsck' to find out what is wrong.",
+        ),
+    }
+}
+
+/// Lock files left behind by a git process that did not finish make every
+/// later write fail.
+fn check_locks(repo: &Repository) -> Check {
+    let locks: Vec<PathBuf> = ["index.lock", "HEAD.lock", "config.lock"]
+        .iter()
+        .map(|x| repo.path().join(x))
+        .filter(|x| x.exists())
+        .collect();
+    if locks.is_empty() {
+        return Check::pass("locks", "no lock files.");
+    }
+    let paths: Vec<String> = locks.iter().map(|x| x.display().to_string()).collect();
+    Check::fail(
+        "locks",
+        format!(
+            "{} exists, so the repo can not be changed.",
+            paths.join(", ")
+        ),
+        "Make sure no git process is running, then delete the lock files.",
+    )
+}
+
+/// Returns the path a remote url points to, or None if it is not local.
+fn local_remote_path(url: &str, home_dir: &Path) -> Option<PathBuf> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return Some(PathBuf::from(path));
+    }
+    if url.contains("://") {
+        return None;
+    }
+    // An scp style url like host:path.
+    if let Some((host, _)) = url.split_once(':') {
+        if !host.contains('/') {
+            return None;
+        }
+    }
+    // Commands that reach remotes run in $HOME.
+    Some(home_dir.join(url))
+}
+
+fn check_remotes(repo: &Repository, home_dir: &Path) -> Vec<Check> {
+    let names: Vec<String> = match repo.remotes() {
+        Ok(names) => names.iter().flatten().map(String::from).collect(),
+        Err(_) => Vec::new(),
+    };
+    if names.is_empty() {
+        return vec![Check::warn(
+            "remote",
+            "there are no remotes, so nothing can be pushed or pulled.",
+            "Run 'git home remote add origin <url>'.",
+        )];
+    }
+
+    let mut checks = Vec::new();
+    for name in names {
+        let url = match repo
+            .find_remote(&name)
+            .ok()
+            .and_then(|x| x.url().map(String::from))
+        {
+            Some(url) => url,
+            None => {
+                checks.push(Check::fail(
+                    "remote",
+                    format!("{} has no url.", name),
+                    format!("Run 'git home remote set-url {} <url>'.", name),
+                ));
+                continue;
+            }
+        };
+        let check = match local_remote_path(&url, home_dir) {
+            None => Check::pass(
+                "remote",
+                format!("{} {} (not checked, it is not local).", name, url),
+            ),
+            Some(path) if Repository::open(&path).is_ok() => {
+                Check::pass("remote", format!("{} {}", name, url))
+            }
+            Some(path) => Check::fail(
+                "remote",
+                format!(
+                    "{} points to {}, which is not a git repo.",
+                    name,
+                    path.display()
+                ),
+                format!("Run 'git home remote set-url {} <url>'.", name),
+            ),
+        };
+        checks.push(check);
+    }
+    checks
+}
+
+/// `git home --` hides untracked files, but plain git run on the repo lists
+/// every file in $HOME without this setting.
+fn check_untracked_setting(repo: &Repository) -> Check {
+    let value = repo
+        .config()
+        .and_then(|config| config.get_string("status.showUntrackedFiles"));
+    match value.as_deref() {
+        Ok("no") => Check::pass("untracked", "status.showUntrackedFiles is no."),
+        _ => Check::warn(
+            "untracked",
+            "status.showUntrackedFiles is not no, so git status outside git home lists all of $HOME.",
+            "Run 'git home -- config status.showUntrackedFiles no'.",
+        ),
+    }
+}
+
+/// Reads every object in the repo and the index.
+fn check_integrity(repo: &Repository) -> Check {
+    let fix = "Run 'git home -- fsck' to find out what is wrong.";
+    let odb = match repo.odb() {
+        Ok(odb) => odb,
+        Err(e) => {
+            return Check::fail(
+                "integrity",
+                format!("the objects can not be read: {}", e.message()),
+                fix,
+            )
+        }
+    };
+    let mut count = 0;
+    let mut broken = Vec::new();
+    let walked = odb.foreach(|oid| {
+        count += 1;
+        if odb.read(*oid).is_err() {
+            broken.push(oid.to_string());
+        }
+        true
+    });
+    if let Err(e) = walked {
+        return Check::fail(
+            "integrity",
+            format!("the objects can not be listed: {}", e.message()),
+            fix,
+        );
+    }
+    if !broken.is_empty() {
+        return Check::fail(
+            "integrity",
+            format!(
+                "{} objects can not be read: {}",
+                broken.len(),
+                broken.join(", ")
+            ),
+            fix,
+        );
+    }
+    if let Err(e) = repo.index() {
+        return Check::fail(
+            "integrity",
+            format!("the index can not be read: {}", e.message()),
+            fix,
+        );
+    }
+    Check::pass("integrity", format!("{} objects read.", count))
+}
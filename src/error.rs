@@ -0,0 +1,269 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The errors returned by the library.
+//!
+//! Every failure is an [`Error`], and each kind of error has a stable exit
+//! code, modeled on sysexits.h, that `git home` exits with. The codes are
+//! listed in [`EXIT_CODES`].
+
+use super::{GIT_HOME_CONFIG_FILE, GIT_HOME_KEY_FILE};
+use crate::ignore::IGNORE_FILE;
+use std::{fmt, io};
+
+#[derive(Debug)]
+pub enum Error {
+    Git(git2::Error),
+    Io(io::Error),
+    /// The command line is wrong. Holds what is wrong with it.
+    Usage(String),
+    /// There is no home repo at the given path.
+    NoRepo(String),
+    /// The index has no changes from HEAD.
+    NothingToCommit,
+    /// The commit message is empty.
+    EmptyMessage,
+    /// A revision, branch, remote or tracked file does not exist. Holds a
+    /// message naming it.
+    NotFound(String),
+    /// git home refuses to do what was asked. Holds the reason.
+    Refused(String),
+    /// Paths that were not added because they are ignored.
+    Ignored(Vec<String>),
+    /// The path, relative to $HOME, can not be tracked.
+    InvalidPath(String),
+    /// The path is not inside the user's home directory.
+    OutsideHome(String),
+    /// Local changes to `paths` would be lost, so nothing was changed.
+    Conflict {
+        message: String,
+        paths: Vec<String>,
+        hint: String,
+    },
+    /// A secret could not be encrypted as there is no key.
+    NoKey(String),
+    /// `user.name` or `user.email` is not set.
+    NoSignature,
+    /// The environment variable is not set.
+    MissingEnv(&'static str),
+    /// The config file can not be parsed. Holds what is wrong with it.
+    BadConfig(String),
+    /// `git home doctor` found this many problems.
+    ChecksFailed(usize),
+    /// What was being done when the inner error happened.
+    Context(String, Box<Error>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Every code [`Error::exit_code`] returns, with what it means.
+pub const EXIT_CODES: &[(i32, &str)] = &[
+    (1, "there is nothing to commit."),
+    (2, "the commit message is empty, nothing was committed."),
+    (3, "doctor found a problem."),
+    (64, "the command line is wrong."),
+    (65, "a path, revision, branch or remote is unusable."),
+    (66, "there is no home repo."),
+    (74, "reading or writing the repo or a file failed."),
+    (75, "local changes are in the way, nothing was done."),
+    (
+        78,
+        "the environment, git config or config file has missing or bad values.",
+    ),
+];
+
+impl Error {
+    /// The code `git home` exits with when it fails with this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NothingToCommit => 1,
+            Error::EmptyMessage => 2,
+            Error::ChecksFailed(_) => 3,
+            Error::Usage(_) => 64,
+            Error::NotFound(_)
+            | Error::Refused(_)
+            | Error::Ignored(_)
+            | Error::InvalidPath(_)
+            | Error::OutsideHome(_) => 65,
+            Error::NoRepo(_) => 66,
+            Error::Git(_) | Error::Io(_) => 74,
+            Error::Conflict { .. } => 75,
+            Error::NoKey(_) | Error::NoSignature | Error::MissingEnv(_) | Error::BadConfig(_) => 78,
+            Error::Context(_, inner) => inner.exit_code(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Git(e) => write!(f, "{}", e.message()),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Usage(message) | Error::NotFound(message) | Error::Refused(message) => {
+                write!(f, "{}", message)
+            }
+            Error::NoRepo(path) => write!(
+                f,
+                "There is no git home repo at {}.\n\
+                 You can create one at any time by running 'git home init'.",
+                path
+            ),
+            Error::NothingToCommit => write!(f, "Nothing to commit, add some changes first."),
+            Error::EmptyMessage => write!(f, "Commit aborted due to an empty commit message."),
+            Error::Ignored(paths) => {
+                writeln!(
+                    f,
+                    "The following paths are ignored by $HOME/{}:",
+                    IGNORE_FILE
+                )?;
+                for i in paths {
+                    writeln!(f, "\t$HOME/{}", i)?;
+                }
+                write!(f, "Use -f if you really want to add them.")
+            }
+            Error::InvalidPath(path) => write!(f, "Refusing to track $HOME/{}", path),
+            Error::OutsideHome(path) => {
+                write!(
+                    f,
+                    "{} is not in your home directory, git home only tracks files there.",
+                    path
+                )
+            }
+            Error::Conflict {
+                message,
+                paths,
+                hint,
+            } => {
+                writeln!(f, "{}", message)?;
+                for i in paths {
+                    writeln!(f, "\t$HOME/{}", i)?;
+                }
+                write!(f, "{}", hint)
+            }
+            Error::NoKey(path) => write!(
+                f,
+                "No key to encrypt $HOME/{} with. Set GIT_HOME_PASSPHRASE or run\n\
+                 'git home secrets add' to create $HOME/{}.",
+                path, GIT_HOME_KEY_FILE
+            ),
+            Error::NoSignature => write!(
+                f,
+                "Unable to create a commit signiture.\n\
+                 Perhaps 'user.name' and 'user.email' are not set"
+            ),
+            Error::MissingEnv(name) => write!(f, "${} is not set.", name),
+            Error::BadConfig(message) => {
+                write!(f, "Invalid $HOME/{}: {}", GIT_HOME_CONFIG_FILE, message)
+            }
+            Error::ChecksFailed(1) => write!(f, "1 check failed."),
+            Error::ChecksFailed(count) => write!(f, "{} checks failed.", count),
+            Error::Context(context, inner) => write!(f, "{}: {}", context, inner),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Git(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Context(_, inner) => Some(inner.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<git2::Error> for Error {
+    fn from(e: git2::Error) -> Error {
+        Error::Git(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+/// Adds what was being done to the error of a result, keeping its exit code.
+pub trait Context<T> {
+    fn context(self, context: impl Into<String>) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        self.map_err(|e| Error::Context(context.into(), Box::new(e.into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        let errors = [
+            (Error::NothingToCommit, 1),
+            (Error::EmptyMessage, 2),
+            (Error::ChecksFailed(1), 3),
+            (Error::Usage(String::new()), 64),
+            (Error::NotFound(String::new()), 65),
+            (Error::Refused(String::new()), 65),
+            (Error::Ignored(Vec::new()), 65),
+            (Error::InvalidPath(String::new()), 65),
+            (Error::OutsideHome(String::new()), 65),
+            (Error::NoRepo(String::new()), 66),
+            (Error::Git(git2::Error::from_str("")), 74),
+            (Error::Io(io::Error::other("")), 74),
+            (
+                Error::Conflict {
+                    message: String::new(),
+                    paths: Vec::new(),
+                    hint: String::new(),
+                },
+                75,
+            ),
+            (Error::NoKey(String::new()), 78),
+            (Error::NoSignature, 78),
+            (Error::MissingEnv("HOME"), 78),
+            (Error::BadConfig(String::new()), 78),
+        ];
+        for (error, code) in errors {
+            assert_eq!(error.exit_code(), code, "{:?}", error);
+            assert!(
+                EXIT_CODES.iter().any(|(x, _)| *x == code),
+                "{} is not documented",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn context_keeps_the_exit_code() {
+        let error: Result<()> = Err(Error::EmptyMessage);
+        assert_eq!(
+            error.context("Could not commit").unwrap_err().exit_code(),
+            2
+        );
+    }
+
+    #[test]
+    fn documented_codes_are_distinct() {
+        for (i, (code, _)) in EXIT_CODES.iter().enumerate() {
+            assert!(EXIT_CODES[i + 1..].iter().all(|(x, _)| x != code));
+        }
+    }
+}
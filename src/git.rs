@@ -14,328 +14,788 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use super::GIT_HOME_DIR;
-use git2::{Object, Repository, Signature, StatusOptions, Tree};
-use std::result;
-use std::{env, io, io::prelude::*, path::Path, process::exit};
-const RED: &str = "\x1b[31m";
-const GREEN: &str = "\x1b[32m";
-const RESET: &str = "\x1b[0m";
-/// Gets the absolute path of the git_home_directory.
+use super::{Environment, GIT_HOME_BACKUP_DIR, GIT_HOME_KEY_FILE};
+use crate::error::{Context, Error, Result};
+use crate::ignore::is_ignored;
+use crate::profile::{get_overlay_dir, strip_overlay};
+use crate::secret::{get_secret_patterns, is_encrypted, is_secret, Key};
+use crate::template::{
+    get_template_paths, get_template_values, is_template, render_template, unrender_template,
+};
+use chrono::Local;
+use git2::{
+    build::CheckoutBuilder, Config, Cred, CredentialType, Delta, Index, IndexEntry, IndexTime,
+    ObjectType, Oid, RemoteCallbacks, Repository, Signature, Status, StatusOptions, StatusShow,
+    Tree, TreeWalkMode, TreeWalkResult,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::os::unix::fs::MetadataExt;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Returns the name of the branch HEAD points to, even before the first commit.
+pub fn current_branch(repo: &Repository) -> Option<String> {
+    let head = repo.find_reference("HEAD").ok()?;
+    let target = head.symbolic_target()?;
+    target.strip_prefix("refs/heads/").map(String::from)
+}
+
+/// Describes where HEAD points, e.g. `On branch main`.
+pub fn gen_branch_string(repo: &Repository) -> String {
+    if let Some(branch) = current_branch(repo) {
+        return format!("On branch {branch}");
+    }
+    match repo.head().ok().and_then(|head| head.target()) {
+        Some(id) => format!("HEAD detached at {}", &id.to_string()[..7]),
+        None => String::from("HEAD is not on a branch"),
+    }
+}
+
+/// Returns the ssh private keys in $HOME/.ssh that can be offered to a remote.
+fn ssh_key_files(home_dir: &Path) -> Vec<PathBuf> {
+    ["id_ed25519", "id_ecdsa", "id_rsa"]
+        .iter()
+        .map(|key| home_dir.join(".ssh").join(key))
+        .filter(|key| key.exists())
+        .collect()
+}
+
+/// Returns the config of the home repo.
+pub fn get_repo_config(repo: &Repository) -> Result<Config> {
+    repo.config().context("Could not open repository config")
+}
+
+/// Makes `branch` push to and pull from the branch of the same name on `remote`.
+pub fn set_branch_upstream(repo: &Repository, branch: &str, remote: &str) -> Result<()> {
+    let mut config = get_repo_config(repo)?;
+    config
+        .set_str(&format!("branch.{branch}.remote"), remote)
+        .and_then(|_| {
+            config.set_str(
+                &format!("branch.{branch}.merge"),
+                &format!("refs/heads/{branch}"),
+            )
+        })
+        .context(format!("Could not set upstream of {}", branch))
+}
+
+/// Progress reported while talking to a remote.
+pub enum Progress<'a> {
+    /// Output from the remote, passed on as is.
+    Remote(&'a [u8]),
+    /// Objects received so far, out of the total.
+    Receiving(usize, usize),
+    /// Objects sent so far, out of the total.
+    Writing(usize, usize),
+}
+
+/// Builds the callbacks used to authenticate with a remote and report
+/// progress to `progress`.
 ///
-/// Returns `Ok(path)` if GIT_HOME_DIR env variable is set else it returns `Err(path)` with the default value.
-pub fn resolve_git_repo() -> result::Result<String, String> {
-    if let Ok(dir) = std::env::var("GIT_HOME_DIR") {
-        Ok(dir)
-    } else {
-        let mut home_dir = if let Ok(home) = std::env::var("HOME") {
-            home
+/// Credentials are tried in order: ssh-agent, ssh key files in
+/// `home_dir/.ssh`, then the credential helper set in `config`.
+pub fn gen_remote_callbacks<'a>(
+    config: Config,
+    home_dir: &Path,
+    progress: fn(Progress),
+) -> RemoteCallbacks<'a> {
+    let mut tried_agent = false;
+    let mut tried_username = false;
+    let mut tried_helper = false;
+    let mut key_files = ssh_key_files(home_dir).into_iter();
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        let user = username.unwrap_or("git");
+        if allowed.contains(CredentialType::USERNAME) && !tried_username {
+            tried_username = true;
+            return Cred::username(user);
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if !tried_agent {
+                tried_agent = true;
+                return Cred::ssh_key_from_agent(user);
+            }
+            if let Some(key) = key_files.next() {
+                return Cred::ssh_key(user, None, &key, None);
+            }
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried_helper {
+            tried_helper = true;
+            return Cred::credential_helper(&config, url, username);
+        }
+        Err(git2::Error::from_str("no usable credentials for remote"))
+    });
+    callbacks.sideband_progress(move |data| {
+        progress(Progress::Remote(data));
+        true
+    });
+    callbacks.transfer_progress(move |stats| {
+        progress(Progress::Receiving(
+            stats.received_objects(),
+            stats.total_objects(),
+        ));
+        true
+    });
+    callbacks.push_transfer_progress(move |current, total, _bytes| {
+        progress(Progress::Writing(current, total));
+    });
+    callbacks
+}
+
+/// How a tracked file has changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    New,
+    Modified,
+    Deleted,
+    Renamed,
+    /// Changed between a file, a symlink or a submodule.
+    TypeChange,
+}
+
+impl ChangeKind {
+    /// The letter `git status --porcelain` uses for the change.
+    pub fn code(&self) -> char {
+        match self {
+            ChangeKind::New => 'A',
+            ChangeKind::Modified => 'M',
+            ChangeKind::Deleted => 'D',
+            ChangeKind::Renamed => 'R',
+            ChangeKind::TypeChange => 'T',
+        }
+    }
+
+    /// The name of the change in `git home status --json`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChangeKind::New => "new",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+            ChangeKind::Renamed => "renamed",
+            ChangeKind::TypeChange => "typechange",
+        }
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChangeKind::New => write!(f, "new file"),
+            ChangeKind::Modified => write!(f, "modified"),
+            ChangeKind::Deleted => write!(f, "deleted"),
+            ChangeKind::Renamed => write!(f, "renamed"),
+            ChangeKind::TypeChange => write!(f, "typechange"),
+        }
+    }
+}
+
+/// A tracked file that differs between HEAD, the index and $HOME.
+#[derive(Debug, PartialEq)]
+pub struct StatusEntry {
+    /// The path of the file relative to $HOME.
+    pub path: String,
+    pub kind: ChangeKind,
+    /// The path the file had before it was renamed.
+    pub old_path: Option<String>,
+}
+
+impl StatusEntry {
+    /// Describes the change the way `git status` does, with paths written
+    /// as `$HOME/path`.
+    pub fn describe(&self) -> String {
+        let label = format!("{}:", self.kind);
+        match &self.old_path {
+            Some(old_path) => format!("{label:<12}$HOME/{old_path} -> $HOME/{}", self.path),
+            None => format!("{label:<12}$HOME/{}", self.path),
+        }
+    }
+}
+
+/// Returns the tracked files in the home repo that differ between HEAD and
+/// the index, or the index and $HOME, depending on `show`.
+///
+/// Templates and secrets whose file in $HOME matches their rendered or
+/// decrypted contents are not reported as changed, nor are files that match
+/// their replacement in the host's overlay directory.
+pub fn get_repo_status(
+    repo: &Repository,
+    show: StatusShow,
+    env: &Environment,
+) -> Result<Vec<StatusEntry>> {
+    let mut options = StatusOptions::new();
+    // Renames in $HOME are only found among the untracked files, which are
+    // not reported themselves.
+    options.include_untracked(!matches!(show, StatusShow::Index));
+    options.renames_head_to_index(true);
+    options.renames_index_to_workdir(true);
+    options.show(show);
+    let statuses = repo.statuses(Some(&mut options))?;
+    let home_dir = repo.workdir().unwrap_or_else(|| Path::new(""));
+    let overlay = get_overlay_dir(repo, env);
+    let mut index = None;
+
+    let mut entries = Vec::new();
+    for i in statuses.iter() {
+        let status = i.status();
+        let kind = if status.intersects(Status::INDEX_NEW) {
+            ChangeKind::New
+        } else if status.intersects(Status::INDEX_RENAMED | Status::WT_RENAMED) {
+            ChangeKind::Renamed
+        } else if status.intersects(Status::INDEX_TYPECHANGE | Status::WT_TYPECHANGE) {
+            ChangeKind::TypeChange
+        } else if status.intersects(Status::INDEX_DELETED | Status::WT_DELETED) {
+            ChangeKind::Deleted
+        } else if status.intersects(Status::INDEX_MODIFIED | Status::WT_MODIFIED) {
+            ChangeKind::Modified
         } else {
-            String::new()
+            continue;
+        };
+        let delta = i.head_to_index().or_else(|| i.index_to_workdir());
+        let (old_path, path) = match delta {
+            Some(delta) => (delta.old_file().path(), delta.new_file().path()),
+            None => (None, None),
+        };
+        let path = match path.or(old_path) {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => continue,
         };
-        if home_dir.len() != 0 {
-            home_dir.push('/');
+        let old_path = match kind {
+            ChangeKind::Renamed => old_path.map(|x| x.to_string_lossy().into_owned()),
+            _ => None,
+        };
+
+        if status == Status::WT_MODIFIED {
+            let index = index.get_or_insert_with(|| repo.index().ok());
+            let source = match index.as_ref() {
+                Some(index) => find_overlay_file(index, overlay.as_deref(), &path),
+                None => None,
+            };
+            let source = source.or_else(|| is_filtered(home_dir, &path).then(|| path.clone()));
+            if let Some(source) = source {
+                let expected = index
+                    .as_ref()
+                    .and_then(|index| index.get_path(Path::new(&source), 0))
+                    .and_then(|entry| repo.find_blob(entry.id).ok())
+                    .and_then(|blob| gen_home_contents(home_dir, &source, blob.content(), env));
+                if expected.is_some() && fs::read(home_dir.join(&path)).ok() == expected {
+                    continue;
+                }
+            }
         }
-        Err(format!("{}{}", home_dir, GIT_HOME_DIR))
+        entries.push(StatusEntry {
+            path,
+            kind,
+            old_path,
+        });
     }
+    Ok(entries)
 }
 
-/// Returs the home repository
-pub fn open_home_repo() -> io::Result<Repository> {
-    let git_home_dir = match resolve_git_repo() {
-        Ok(string) | Err(string) => string,
-    };
+/// Returns the untracked files that sit directly in a directory holding
+/// tracked files, leaving out ignored ones and those written from the host's
+/// overlay directory.
+pub fn get_untracked_files(repo: &Repository, env: &Environment) -> Result<Vec<String>> {
+    let home_dir = repo.workdir().unwrap_or_else(|| Path::new(""));
+    let overlay = get_overlay_dir(repo, env);
+    let index = repo.index()?;
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for entry in index.iter() {
+        let path = PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned());
+        if let Some(parent) = path.parent() {
+            if !dirs.iter().any(|x| x == parent) {
+                dirs.push(parent.to_path_buf());
+            }
+        }
+    }
 
-    let home_dir = match env::var("HOME") {
-        Ok(string) => string,
-        Err(_) => {
-            eprintln!("Could not get value of $HOME.");
-            exit(74);
+    let mut untracked = Vec::new();
+    for dir in dirs {
+        let entries = match fs::read_dir(home_dir.join(&dir)) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            if entry.file_type().map_or(true, |kind| kind.is_dir()) {
+                continue;
+            }
+            let path = dir.join(entry.file_name());
+            let path = match path.to_str() {
+                Some(path) => String::from(path),
+                None => continue,
+            };
+            if path != GIT_HOME_KEY_FILE
+                && index.get_path(Path::new(&path), 0).is_none()
+                && find_overlay_file(&index, overlay.as_deref(), &path).is_none()
+                && !is_ignored(repo, &path)
+            {
+                untracked.push(path);
+            }
         }
-    };
+    }
+    untracked.sort();
+    Ok(untracked)
+}
 
-    let repo = match Repository::open_bare(&git_home_dir) {
-        Ok(repo) => repo,
-        Err(_e) => {
-            println!(
-                "Git home repo doesn't exist, create one now at {}?",
-                git_home_dir
-            );
-            print!("(y/n) ");
-            io::stdout().flush()?;
-            let mut buffer = String::new();
-            io::stdin().read_line(&mut buffer)?;
-            buffer.pop();
-            if buffer == "y" {
-                println!("Creating git home repo: {}.", git_home_dir);
-                match Repository::init_bare(&git_home_dir) {
-                    Ok(repo) => repo,
-                    Err(e) => {
-                        eprintln!(
-                            "Could not initialize git_home directory at {}: {}",
-                            git_home_dir, e
-                        );
-                        exit(1);
-                    }
+/// Returns the tracked files with changes in $HOME or in the index.
+pub fn get_dirty_files(repo: &Repository, env: &Environment) -> Result<Vec<String>> {
+    let mut dirty: Vec<String> = Vec::new();
+    for show in [StatusShow::Workdir, StatusShow::Index] {
+        for i in get_repo_status(repo, show, env)? {
+            for path in [Some(i.path), i.old_path].into_iter().flatten() {
+                if !dirty.contains(&path) {
+                    dirty.push(path);
                 }
-            } else {
-                println!("You can create a new repository at any time by running 'git home init'.");
-                exit(1);
             }
+        }
+    }
+    Ok(dirty)
+}
+
+/// Returns the tracked file in the overlay directory `overlay` that replaces
+/// `path` in $HOME, if there is one.
+fn find_overlay_file(index: &Index, overlay: Option<&str>, path: &str) -> Option<String> {
+    let source = format!("{}/{}", overlay?, path);
+    index.get_path(Path::new(&source), 0).map(|_| source)
+}
 
-            // }
+/// Adds the file at `path`, relative to $HOME, to the index.
+///
+/// Templates are stored with their placeholders put back and secrets are
+/// stored encrypted. Files the host's overlay directory replaces are copied
+/// back into it and staged there, leaving the shared version alone.
+pub fn stage_path(
+    repo: &Repository,
+    index: &mut Index,
+    path: &Path,
+    env: &Environment,
+) -> Result<()> {
+    let home_dir = repo.workdir().unwrap_or_else(|| Path::new(""));
+    let path_str = path.to_string_lossy();
+    if path_str == GIT_HOME_KEY_FILE {
+        return Err(Error::InvalidPath(String::from(GIT_HOME_KEY_FILE)));
+    }
+    let overlay = get_overlay_dir(repo, env);
+    if let Some(source) = find_overlay_file(index, overlay.as_deref(), &path_str) {
+        fs::copy(home_dir.join(path), home_dir.join(&source)).context(format!(
+            "Could not copy $HOME/{} into $HOME/{}",
+            path_str, source
+        ))?;
+        return stage_path(repo, index, Path::new(&source), env);
+    }
+    let template = is_template(home_dir, &path_str);
+    let secret = is_secret(home_dir, &path_str);
+    if !template && !secret {
+        index.add_path(path)?;
+        return Ok(());
+    }
+
+    let full_path = home_dir.join(path);
+    let meta = fs::metadata(&full_path)?;
+    let mut contents = fs::read(&full_path)?;
+    let previous = index
+        .get_path(path, 0)
+        .and_then(|entry| repo.find_blob(entry.id).ok())
+        .map(|blob| blob.content().to_vec());
+    let previous_plain = match &previous {
+        Some(previous) if is_encrypted(previous) => {
+            Key::load(home_dir, env).and_then(|key| key.decrypt(previous))
         }
+        Some(previous) => Some(previous.clone()),
+        None => None,
+    };
+
+    if template {
+        let rendered = String::from_utf8_lossy(&contents);
+        let previous = previous_plain.as_ref().map(|x| String::from_utf8_lossy(x));
+        let values = get_template_values(home_dir, env);
+        contents = unrender_template(previous.as_deref(), &rendered, &values).into_bytes();
+    }
+    if secret {
+        contents = match (previous, previous_plain) {
+            // Encryption is not deterministic, so unchanged files keep their
+            // old blob instead of showing up as modified.
+            (Some(previous), Some(plain)) if is_encrypted(&previous) && plain == contents => {
+                previous
+            }
+            _ => match Key::load(home_dir, env) {
+                Some(key) => key.encrypt(&contents)?,
+                None => return Err(Error::NoKey(path_str.into_owned())),
+            },
+        };
+    }
+
+    // The entry is built by hand, as `add_path` would write the plaintext
+    // into the object database.
+    let entry = IndexEntry {
+        ctime: IndexTime::new(meta.ctime() as i32, meta.ctime_nsec() as u32),
+        mtime: IndexTime::new(meta.mtime() as i32, meta.mtime_nsec() as u32),
+        dev: meta.dev() as u32,
+        ino: meta.ino() as u32,
+        mode: if meta.mode() & 0o111 != 0 {
+            0o100755
+        } else {
+            0o100644
+        },
+        uid: meta.uid(),
+        gid: meta.gid(),
+        file_size: meta.size() as u32,
+        id: Oid::zero(),
+        flags: path_str.len().min(0xfff) as u16,
+        flags_extended: 0,
+        path: path_str.as_bytes().to_vec(),
     };
+    index.add_frombuffer(&entry, &contents)?;
+    Ok(())
+}
 
-    let path = Path::new(&home_dir);
-    if let Err(e) = repo.set_workdir(&path, false) {
-        eprintln!("Could not set working dir to {}", e);
-        exit(1);
+/// Returns what a tracked file with the contents `blob` looks like in $HOME:
+/// decrypted if it is a secret and rendered if it is a template.
+///
+/// Returns None if it is encrypted with a key this machine does not have.
+pub fn gen_home_contents(
+    home_dir: &Path,
+    path: &str,
+    blob: &[u8],
+    env: &Environment,
+) -> Option<Vec<u8>> {
+    let mut contents = match is_encrypted(blob) {
+        true => Key::load(home_dir, env)?.decrypt(blob)?,
+        false => blob.to_vec(),
     };
-    Ok(repo)
+    if is_template(home_dir, path) {
+        let template = String::from_utf8_lossy(&contents);
+        contents = render_template(&template, &get_template_values(home_dir, env)).into_bytes();
+    }
+    Some(contents)
 }
 
-/// Prints the satus of the home repo.
-pub fn print_repo_status(has_color: bool) -> io::Result<()> {
-    let repo = open_home_repo()?;
-    let mut up_to_date = true;
-    let mut options = StatusOptions::new();
-    options.include_untracked(false);
-    options.show(git2::StatusShow::Workdir);
-    let status = match repo.statuses(Some(&mut options)) {
-        Ok(status) => status,
-        Err(err) => {
-            eprintln!("Could not get repo status: {}", err);
-            exit(74);
-        }
+/// Returns true if `path` is stored differently from how it appears in $HOME.
+pub fn is_filtered(home_dir: &Path, path: &str) -> bool {
+    is_template(home_dir, path) || is_secret(home_dir, path)
+}
+
+/// Rewrites the templates and secrets among `paths` in $HOME from their
+/// version in `tree` or, without one, the index, then copies the files in the
+/// host's overlay directory over the ones they replace. With no `paths` every
+/// tracked template, secret and overlay file is written.
+///
+/// This is run after anything checks files out into $HOME, which leaves
+/// templates with their placeholders, secrets encrypted and the shared
+/// version of overlaid files. Secrets that can not be decrypted are left as
+/// they are and reported to `env.warn`.
+pub fn write_home_files(
+    repo: &Repository,
+    tree: Option<&Tree>,
+    paths: Option<&[String]>,
+    env: &Environment,
+) -> Result<()> {
+    let home_dir = match repo.workdir() {
+        Some(dir) => dir.to_path_buf(),
+        None => return Ok(()),
     };
-    if status.len() > 0 {
-        up_to_date = false;
-        if has_color {
-            print!("{RED}");
-        }
-        println!("Files with untracked changes:\n\tYou can run 'git home add -u' to add them to the index'");
-        for i in status.iter() {
-            println!(
-                "\t{}",
-                match i.path() {
-                    Some(path) => path,
-                    None => {
-                        if has_color {
-                            print!("{RESET}");
-                        }
-                        eprintln!("Path is not valid utf-8");
-                        exit(1);
-                    }
+    let overlay = get_overlay_dir(repo, env);
+    if get_template_paths(&home_dir).is_empty()
+        && get_secret_patterns(&home_dir).is_empty()
+        && overlay.is_none()
+    {
+        return Ok(());
+    }
+    let index = repo.index().context("Could not open index")?;
+
+    let mut entries: Vec<(String, Oid)> = Vec::new();
+    match tree {
+        Some(tree) => {
+            let _ = tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+                if entry.kind() == Some(ObjectType::Blob) {
+                    entries.push((
+                        format!("{}{}", root, entry.name().unwrap_or("")),
+                        entry.id(),
+                    ));
                 }
-            )
+                TreeWalkResult::Ok
+            });
         }
-        if has_color {
-            print!("{RESET}");
+        None => {
+            for entry in index.iter() {
+                entries.push((String::from_utf8_lossy(&entry.path).into_owned(), entry.id));
+            }
         }
     }
 
-    let mut options = StatusOptions::new();
-    options.include_untracked(false);
-    options.show(git2::StatusShow::Index);
-    let status = match repo.statuses(Some(&mut options)) {
-        Ok(status) => status,
-        Err(err) => {
-            eprintln!("Could not get repo status: {}", err);
-            exit(74);
-        }
+    let overlaid: HashSet<String> = match &overlay {
+        Some(overlay) => entries
+            .iter()
+            .filter_map(|(path, _)| strip_overlay(overlay, path))
+            .map(String::from)
+            .collect(),
+        None => HashSet::new(),
     };
-    if status.len() > 0 {
-        up_to_date = false;
-        if has_color {
-            print!("{GREEN}");
+    for (path, id) in entries {
+        // The overlay's version wins, whichever order the two come in.
+        let target = match overlay
+            .as_deref()
+            .and_then(|overlay| strip_overlay(overlay, &path))
+        {
+            Some(target) => String::from(target),
+            None if overlaid.contains(&path) => continue,
+            None if is_filtered(&home_dir, &path) => path.clone(),
+            None => continue,
+        };
+        if paths.is_some_and(|paths| !paths.contains(&path) && !paths.contains(&target)) {
+            continue;
         }
-        println!("Files with changes to be commited:");
-        for i in status.iter() {
-            println!(
-                "\t$HOME/{}",
-                match i.path() {
-                    Some(path) => path,
-                    None => {
-                        if has_color {
-                            print!("{RESET}");
-                        }
-                        eprintln!("Path is not valid utf-8");
-                        exit(1);
-                    }
-                }
-            )
+        let blob = match repo.find_blob(id) {
+            Ok(blob) => blob,
+            Err(_) => continue,
+        };
+        let contents = match gen_home_contents(&home_dir, &path, blob.content(), env) {
+            Some(contents) => contents,
+            None => {
+                (env.warn)(&format!(
+                    "could not decrypt $HOME/{}, the key is missing or wrong",
+                    path
+                ));
+                continue;
+            }
+        };
+        let full_path = home_dir.join(&target);
+        if fs::read(&full_path).ok().as_deref() == Some(contents.as_slice()) {
+            continue;
         }
-        if has_color {
-            print!("{RESET}");
+        match full_path.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
         }
+        .and_then(|_| fs::write(&full_path, contents))
+        .context(format!("Could not write $HOME/{}", target))?;
     }
+    Ok(())
+}
 
-    if up_to_date {
-        println!("Everything is upto date.");
+/// Replaces the tracked files that differ between `from` and `to` with their
+/// version in `to`, rendering templates and decrypting secrets among them.
+///
+/// Callers are expected to have checked `find_overwritten_files` first, as
+/// the files are overwritten without further checks.
+pub fn checkout_changed_files(
+    repo: &Repository,
+    from: Option<&Tree>,
+    to: &Tree,
+    env: &Environment,
+) -> Result<()> {
+    let diff = repo
+        .diff_tree_to_tree(from, Some(to), None)
+        .context("Could not compare trees")?;
+    let changed: Vec<String> = diff
+        .deltas()
+        .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    if changed.is_empty() {
+        return Ok(());
     }
-    Ok(())
+
+    let mut checkout = CheckoutBuilder::new();
+    checkout.force();
+    for i in changed.iter() {
+        checkout.path(i);
+    }
+    repo.checkout_tree(to.as_object(), Some(&mut checkout))
+        .context("Could not update files in $HOME")?;
+    write_home_files(repo, Some(to), Some(&changed), env)
 }
 
-/// Returns required arguments for an initial commit
-pub fn gen_init_comimt_args(repo: &Repository) -> io::Result<(Signature<'static>, Tree)> {
-    let sig = match repo.signature() {
-        Ok(sig) => sig,
-        Err(_e) => {
-            eprintln!(
-                "Unable to create a commit signiture.\n\
-		 Perhaps 'user.name' and 'user.email' are not set"
-            );
-            exit(64);
-        }
-    };
-    let mut index = match repo.index() {
-        Ok(index) => index,
-        Err(_e) => {
-            eprintln!("Could not open repository index.");
-            exit(64);
-        }
-    };
-    let tree_id = match index.write_tree() {
-        Ok(id) => id,
-        Err(_e) => {
-            eprintln!("Unable to write initial tree form index.");
-            exit(74);
-        }
-    };
-    let tree = match repo.find_tree(tree_id) {
-        Ok(id) => id,
-        Err(_e) => {
-            eprintln!("Could not look up initial tree");
-            exit(74);
+/// Returns the files in $HOME that would lose changes if the tracked files
+/// were replaced by the contents of `to`.
+///
+/// This covers dirty tracked files that differ between `from` and `to`, as
+/// well as untracked files that a newly tracked file would clobber.
+pub fn find_overwritten_files(
+    repo: &Repository,
+    from: Option<&Tree>,
+    to: &Tree,
+    env: &Environment,
+) -> Result<Vec<String>> {
+    let dirty: HashSet<String> = get_dirty_files(repo, env)?.into_iter().collect();
+
+    let diff = repo
+        .diff_tree_to_tree(from, Some(to), None)
+        .context("Could not compare trees")?;
+    let workdir = repo.workdir().unwrap_or_else(|| Path::new(""));
+    let mut overwritten = Vec::new();
+    for delta in diff.deltas() {
+        let path = match delta.new_file().path().or_else(|| delta.old_file().path()) {
+            Some(path) => path,
+            None => continue,
+        };
+        let path_str = path.to_string_lossy().into_owned();
+        let clobbers_untracked =
+            delta.status() == Delta::Added && workdir.join(path).symlink_metadata().is_ok();
+        if dirty.contains(&path_str) || clobbers_untracked {
+            overwritten.push(path_str);
         }
-    };
-    Ok((sig, tree))
+    }
+    Ok(overwritten)
 }
 
-pub fn gen_commit_args(repo: &Repository) -> io::Result<(Object, Signature<'static>, Tree)> {
-    let (parent, _refrence) = match repo.revparse_ext("HEAD") {
-        Ok(result) => result,
-        Err(_e) => unreachable!(),
-    };
+/// Returns a new timestamped directory to move files displaced from $HOME into.
+pub fn gen_backup_dir(home_dir: &Path) -> PathBuf {
+    let stamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+    let base = home_dir.join(GIT_HOME_BACKUP_DIR);
+    let mut backup_dir = base.join(&stamp);
+    let mut suffix = 1;
+    while backup_dir.exists() {
+        backup_dir = base.join(format!("{stamp}-{suffix}"));
+        suffix += 1;
+    }
+    backup_dir
+}
 
-    let (sig, tree) = gen_init_comimt_args(repo)?;
+/// Moves `$HOME/path` into `backup_dir`, keeping its path relative to $HOME.
+pub fn backup_file(home_dir: &Path, path: &Path, backup_dir: &Path) -> Result<()> {
+    let target = backup_dir.join(path);
+    match target.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+    .and_then(|_| fs::rename(home_dir.join(path), &target))
+    .context(format!("Could not back up $HOME/{}", path.display()))
+}
 
-    Ok((parent, sig, tree))
+/// Returns the signature commits in the home repo are made with.
+pub fn gen_signature(repo: &Repository) -> Result<Signature<'static>> {
+    repo.signature().map_err(|_| Error::NoSignature)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
 
-fn gen_repo_string() -> io::Result<String> {
+    /// A home repo with `.bashrc` and `.profile` committed.
+    fn committed_repo() -> (TempDir, Repository) {
+        let home = tempfile::tempdir().unwrap();
+        let repo = Repository::init_bare(home.path().join(".config/git_home")).unwrap();
+        repo.set_workdir(home.path(), false).unwrap();
+        fs::write(home.path().join(".bashrc"), "alias ls='ls -F'\n").unwrap();
+        fs::write(home.path().join(".profile"), "export EDITOR=vi\n").unwrap();
+        {
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new(".bashrc")).unwrap();
+            index.add_path(Path::new(".profile")).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let sig = Signature::now("Test", "test@example.com").unwrap();
+            repo.commit(Some("HEAD"), &sig, &sig, "Add dotfiles", &tree, &[])
+                .unwrap();
+        }
+        (home, repo)
+    }
 
-    let repo = open_home_repo()?;
-    let mut output = String::new();
+    fn status(repo: &Repository, show: StatusShow) -> Vec<(String, ChangeKind, Option<String>)> {
+        get_repo_status(repo, show, &Environment::default())
+            .unwrap()
+            .into_iter()
+            .map(|x| (x.path, x.kind, x.old_path))
+            .collect()
+    }
 
-    let mut options = StatusOptions::new();
-    options.include_untracked(false);
-    options.show(git2::StatusShow::Workdir);
-    let status = match repo.statuses(Some(&mut options)) {
-        Ok(status) => status,
-        Err(err) => {
-            eprintln!("Could not get repo status: {}", err);
-            exit(74);
-        }
-    };
-    if status.len() > 0 {
-            
-        output.push_str("# Files with untracked changes:\n\tYou can run 'git home add -u' to add them to the index:\n");
-        for i in status.iter() {
-
-            output.push_str(
-             &format!( "#\t{}",
-                match i.path() {
-                    Some(path) => path,
-                    None => {
-                        eprintln!("Path is not valid utf-8");
-                        exit(1);
-                    }
-                }
-             ));
-	    output.push('\n');
-        }
+    fn entry(
+        path: &str,
+        kind: ChangeKind,
+        old_path: Option<&str>,
+    ) -> (String, ChangeKind, Option<String>) {
+        (String::from(path), kind, old_path.map(String::from))
     }
-    
-    
 
-    let mut options = StatusOptions::new();
-    options.include_untracked(false);
-    options.show(git2::StatusShow::Index);
-    let status = match repo.statuses(Some(&mut options)) {
-        Ok(status) => status,
-        Err(err) => {
-            eprintln!("Could not get repo status: {}", err);
-            exit(74);
-        }
-    };
-    if status.len() > 0 {
-        output.push_str("# Files with changes to be commited:\n");
-        for i in status.iter() {
-            output.push_str(&format!(
-                "#\t$HOME/{}",
-                match i.path() {
-                    Some(path) => path,
-                    None => {
-			
-                        eprintln!("Path is not valid utf-8");
-                        exit(1);
-                    }
-                }
-            ));
-	    output.push('\n');
-        }
+    #[test]
+    fn reports_modified_files() {
+        let (home, repo) = committed_repo();
+        fs::write(home.path().join(".bashrc"), "alias ls='ls -G'\n").unwrap();
+        assert_eq!(
+            status(&repo, StatusShow::Workdir),
+            [entry(".bashrc", ChangeKind::Modified, None)]
+        );
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(".bashrc")).unwrap();
+        index.write().unwrap();
+        assert_eq!(
+            status(&repo, StatusShow::Index),
+            [entry(".bashrc", ChangeKind::Modified, None)]
+        );
+        assert!(status(&repo, StatusShow::Workdir).is_empty());
     }
 
-    Ok(output)
-}
+    #[test]
+    fn reports_deleted_files() {
+        let (home, repo) = committed_repo();
+        fs::remove_file(home.path().join(".profile")).unwrap();
+        assert_eq!(
+            status(&repo, StatusShow::Workdir),
+            [entry(".profile", ChangeKind::Deleted, None)]
+        );
+
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new(".profile")).unwrap();
+        index.write().unwrap();
+        assert_eq!(
+            status(&repo, StatusShow::Index),
+            [entry(".profile", ChangeKind::Deleted, None)]
+        );
+    }
 
+    #[test]
+    fn reports_renamed_files() {
+        let (home, repo) = committed_repo();
+        fs::rename(
+            home.path().join(".bashrc"),
+            home.path().join(".bash_aliases"),
+        )
+        .unwrap();
+        assert_eq!(
+            status(&repo, StatusShow::Workdir),
+            [entry(".bash_aliases", ChangeKind::Renamed, Some(".bashrc"))]
+        );
 
-#[allow(rustdoc::invalid_rust_codeblocks)]
-/**
-```no_run
-Please enter the commit message for your changes. Lines starting
-with '#' will be ignored, and an empty message aborts the commit.
-
-On branch main
-Your branch is up to date with 'origin/main'.
-
-Changes to be committed:
-modified:   ../../Cargo.lock
-modified:   ../../Cargo.toml
-modified:   mod.rs
-modified:   usage.rs
-modified:   ../git.rs
-modified:   ../main.rs
-modified:   ../run.rs
-modified:   ../../todo.org
-
-```
-
- **/
-pub fn gen_commit_template() -> String {
-    let mut output =  String::from(
-        "
-# Please enter the commit message for your changes. Lines starting
-# with '#' will be ignored, and an empty message aborts the commit.
-#\n");
-    let extension = match gen_repo_string() {
-	Ok(string) => string,
-	Err(e) => {
-	    eprintln!("unable to generate repo status info: {}", e);
-	    exit(74);
-	}
-    };
-    output.push_str(&extension);
-    output
-}
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new(".bashrc")).unwrap();
+        index.add_path(Path::new(".bash_aliases")).unwrap();
+        index.write().unwrap();
+        assert_eq!(
+            status(&repo, StatusShow::Index),
+            [entry(".bash_aliases", ChangeKind::Renamed, Some(".bashrc"))]
+        );
+        assert!(status(&repo, StatusShow::Workdir).is_empty());
+    }
 
-pub fn strip_commit_template(string: String) -> String {
-    let return_val = string
-        .lines()
-        .filter(|x| x.chars().nth(0) != Some('#'))
-        .collect();
-    return_val
+    #[test]
+    fn reports_type_changes() {
+        let (home, repo) = committed_repo();
+        fs::remove_file(home.path().join(".profile")).unwrap();
+        symlink(".bashrc", home.path().join(".profile")).unwrap();
+        assert_eq!(
+            status(&repo, StatusShow::Workdir),
+            [entry(".profile", ChangeKind::TypeChange, None)]
+        );
+    }
+
+    #[test]
+    fn lists_untracked_files_without_reporting_them_as_changes() {
+        let (home, repo) = committed_repo();
+        fs::write(home.path().join(".vimrc"), "set number\n").unwrap();
+        fs::create_dir(home.path().join("notes")).unwrap();
+        fs::write(home.path().join("notes/todo"), "\n").unwrap();
+
+        assert!(status(&repo, StatusShow::Workdir).is_empty());
+        assert_eq!(
+            get_untracked_files(&repo, &Environment::default()).unwrap(),
+            [".vimrc"]
+        );
+    }
 }
@@ -0,0 +1,659 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The home repo as a library.
+//!
+//! [`GitHome`] does not read the environment: the caller says where the repo
+//! and $HOME are and passes in an [`Environment`], and gets back structured
+//! results instead of printed output.
+
+use crate::error::{Error, Result};
+use crate::git::{
+    current_branch, gen_signature, get_repo_status, get_untracked_files, stage_path, ChangeKind,
+    StatusEntry,
+};
+use crate::ignore::{is_ignored, load_ignore_rules, IGNORE_FILE};
+use crate::Environment;
+use git2::{
+    BranchType, Commit, DiffOptions, IndexAddOption, ObjectType, Oid, Repository, Sort, StatusShow,
+    Tree, TreeWalkMode, TreeWalkResult,
+};
+use std::path::Path;
+
+/// A home repo: a bare repo whose work tree is $HOME.
+pub struct GitHome {
+    repo: Repository,
+    env: Environment,
+}
+
+/// The state of the home repo, as shown by `git home status`.
+#[derive(Debug)]
+pub struct Status {
+    /// The checked out branch, or None if HEAD is detached.
+    pub branch: Option<String>,
+    pub upstream: Option<Upstream>,
+    /// Changes in the index that will go into the next commit.
+    pub staged: Vec<StatusEntry>,
+    /// Changes in $HOME that are not staged.
+    pub unstaged: Vec<StatusEntry>,
+}
+
+/// The branch the current branch tracks.
+#[derive(Debug)]
+pub struct Upstream {
+    pub name: String,
+    /// Commits on the current branch that are not on the upstream.
+    pub ahead: usize,
+    /// Commits on the upstream that are not on the current branch.
+    pub behind: usize,
+}
+
+/// Which commits [`GitHome::log`] returns.
+#[derive(Debug, Default)]
+pub struct LogOptions {
+    /// The most commits to return.
+    pub count: Option<usize>,
+    /// Only commits made at or after this unix timestamp.
+    pub since: Option<i64>,
+    /// Only commits made at or before this unix timestamp.
+    pub until: Option<i64>,
+    /// Only commits changing one of these paths, relative to $HOME.
+    pub paths: Vec<String>,
+}
+
+/// A commit returned by [`GitHome::log`].
+#[derive(Debug)]
+pub struct LogEntry {
+    pub id: Oid,
+    pub author: String,
+    pub email: String,
+    /// When the commit was made, as a unix timestamp.
+    pub time: i64,
+    pub message: String,
+}
+
+impl LogEntry {
+    /// The first line of the commit message.
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+}
+
+impl From<&Commit<'_>> for LogEntry {
+    fn from(commit: &Commit) -> LogEntry {
+        let author = commit.author();
+        LogEntry {
+            id: commit.id(),
+            author: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+            email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+            time: commit.time().seconds(),
+            message: String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+        }
+    }
+}
+
+/// A file in the index or a commit, as returned by [`GitHome::tracked`].
+#[derive(Debug)]
+pub struct TrackedFile {
+    /// The path of the file relative to $HOME.
+    pub path: String,
+    /// The git file mode, such as 0o100644.
+    pub mode: u32,
+    /// The size of the contents stored in the repo, in bytes. Templates and
+    /// secrets are stored unrendered and encrypted.
+    pub size: usize,
+}
+
+impl GitHome {
+    /// Opens the home repo at `git_dir`, tracking files in `home_dir`.
+    pub fn open(
+        git_dir: impl AsRef<Path>,
+        home_dir: impl AsRef<Path>,
+        env: Environment,
+    ) -> Result<GitHome> {
+        GitHome::with_home_dir(Repository::open_bare(git_dir)?, home_dir.as_ref(), env)
+    }
+
+    /// Creates a new home repo at `git_dir`, tracking files in `home_dir`.
+    pub fn init(
+        git_dir: impl AsRef<Path>,
+        home_dir: impl AsRef<Path>,
+        env: Environment,
+    ) -> Result<GitHome> {
+        GitHome::with_home_dir(Repository::init_bare(git_dir)?, home_dir.as_ref(), env)
+    }
+
+    fn with_home_dir(repo: Repository, home_dir: &Path, env: Environment) -> Result<GitHome> {
+        repo.set_workdir(home_dir, false)?;
+        if let Err(e) = load_ignore_rules(&repo, home_dir) {
+            (env.warn)(&format!("could not load $HOME/{}: {}", IGNORE_FILE, e));
+        }
+        Ok(GitHome { repo, env })
+    }
+
+    /// The underlying repo, with $HOME as its work tree.
+    pub fn repo(&self) -> &Repository {
+        &self.repo
+    }
+
+    /// Gives up the [`Environment`] and returns the underlying repo.
+    pub fn into_repo(self) -> Repository {
+        self.repo
+    }
+
+    /// What the home repo was opened with in place of the environment.
+    pub fn env(&self) -> &Environment {
+        &self.env
+    }
+
+    /// The directory the tracked files live in.
+    pub fn home_dir(&self) -> &Path {
+        self.repo.workdir().unwrap_or_else(|| Path::new(""))
+    }
+
+    /// Stages `paths`, relative to $HOME. Directories add the files in them
+    /// that are not ignored, or all of them with `force`.
+    ///
+    /// Returns the files staged. If any of `paths` is an untracked ignored
+    /// file and `force` is not set, nothing is staged and
+    /// [`Error::Ignored`] lists them.
+    pub fn add(&self, paths: &[String], force: bool) -> Result<Vec<String>> {
+        let mut index = self.repo.index()?;
+        let mut staged = Vec::new();
+        let mut ignored = Vec::new();
+        // The repo may live in $HOME, its own files are never tracked.
+        let repo_dir = self.repo.path().strip_prefix(self.home_dir()).ok();
+        for i in paths {
+            if self.home_dir().join(i).is_dir() {
+                // Only collects the files, they are staged below so
+                // templates and secrets are handled.
+                let flags = match force {
+                    true => IndexAddOption::FORCE,
+                    false => IndexAddOption::DEFAULT,
+                };
+                index.add_all(
+                    [i],
+                    flags,
+                    Some(&mut |path: &Path, _: &[u8]| {
+                        if !repo_dir.is_some_and(|dir| path.starts_with(dir)) {
+                            staged.push(path.to_string_lossy().into_owned());
+                        }
+                        1
+                    }),
+                )?;
+            } else if !force
+                && index.get_path(Path::new(i), 0).is_none()
+                && is_ignored(&self.repo, i)
+            {
+                ignored.push(i.clone());
+            } else {
+                staged.push(i.clone());
+            }
+        }
+        if !ignored.is_empty() {
+            return Err(Error::Ignored(ignored));
+        }
+
+        for i in staged.iter() {
+            stage_path(&self.repo, &mut index, Path::new(i), &self.env)?;
+        }
+        index.write()?;
+        Ok(staged)
+    }
+
+    /// Stages the changes to every tracked file in $HOME, removing deleted
+    /// files from the index and moving renamed ones. Returns the files staged.
+    pub fn add_updated(&self) -> Result<Vec<String>> {
+        let mut index = self.repo.index()?;
+        let unstaged = get_repo_status(&self.repo, StatusShow::Workdir, &self.env)?;
+        for i in unstaged.iter() {
+            if let Some(old_path) = &i.old_path {
+                index.remove_path(Path::new(old_path))?;
+            }
+            match i.kind {
+                ChangeKind::Deleted => index.remove_path(Path::new(&i.path))?,
+                _ => stage_path(&self.repo, &mut index, Path::new(&i.path), &self.env)?,
+            }
+        }
+        if !unstaged.is_empty() {
+            index.write()?;
+        }
+        Ok(unstaged.into_iter().map(|x| x.path).collect())
+    }
+
+    /// Returns the branch, its upstream and the changed tracked files.
+    pub fn status(&self) -> Result<Status> {
+        Ok(Status {
+            branch: current_branch(&self.repo),
+            upstream: self.upstream(),
+            staged: get_repo_status(&self.repo, StatusShow::Index, &self.env)?,
+            unstaged: get_repo_status(&self.repo, StatusShow::Workdir, &self.env)?,
+        })
+    }
+
+    /// Returns the untracked files that sit directly in a directory holding
+    /// tracked files, leaving out ignored ones.
+    pub fn untracked(&self) -> Result<Vec<String>> {
+        get_untracked_files(&self.repo, &self.env)
+    }
+
+    fn upstream(&self) -> Option<Upstream> {
+        let repo = &self.repo;
+        let branch = repo
+            .find_branch(&current_branch(repo)?, BranchType::Local)
+            .ok()?;
+        let upstream = branch.upstream().ok()?;
+        let name = String::from(upstream.name().ok()??);
+        let (ahead, behind) = repo
+            .graph_ahead_behind(branch.get().target()?, upstream.get().target()?)
+            .ok()?;
+        Some(Upstream {
+            name,
+            ahead,
+            behind,
+        })
+    }
+
+    /// Commits the index to HEAD with `message`, creating the first commit
+    /// if there is none yet. Returns the id of the new commit.
+    ///
+    /// Fails with [`Error::NothingToCommit`] if the index matches HEAD and
+    /// [`Error::EmptyMessage`] if `message` is blank.
+    pub fn commit(&self, message: &str) -> Result<Oid> {
+        if message.trim().is_empty() {
+            return Err(Error::EmptyMessage);
+        }
+        let repo = &self.repo;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let parent = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(_) => None,
+        };
+        let unchanged = match &parent {
+            Some(parent) => parent.tree_id() == tree.id(),
+            None => tree.is_empty(),
+        };
+        if unchanged {
+            return Err(Error::NothingToCommit);
+        }
+        let sig = gen_signature(repo)?;
+        let parents: Vec<&Commit> = parent.iter().collect();
+        Ok(repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)?)
+    }
+
+    /// Returns the files in the index, or in `tree` if given, sorted by path.
+    pub fn tracked(&self, tree: Option<&Tree>) -> Result<Vec<TrackedFile>> {
+        let odb = self.repo.odb()?;
+        let size = |id: Oid| odb.read_header(id).map(|(size, _)| size);
+        let mut files = Vec::new();
+        match tree {
+            Some(tree) => {
+                let mut error = None;
+                tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+                    if entry.kind() != Some(ObjectType::Blob) {
+                        return TreeWalkResult::Ok;
+                    }
+                    match size(entry.id()) {
+                        Ok(size) => files.push(TrackedFile {
+                            path: format!("{}{}", root, entry.name().unwrap_or_default()),
+                            mode: entry.filemode() as u32,
+                            size,
+                        }),
+                        Err(e) => {
+                            error = Some(e);
+                            return TreeWalkResult::Abort;
+                        }
+                    }
+                    TreeWalkResult::Ok
+                })?;
+                if let Some(e) = error {
+                    return Err(e.into());
+                }
+            }
+            None => {
+                for entry in self.repo.index()?.iter() {
+                    files.push(TrackedFile {
+                        path: String::from_utf8_lossy(&entry.path).into_owned(),
+                        mode: entry.mode,
+                        size: size(entry.id)?,
+                    });
+                }
+            }
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        // A conflicted file is in the index once for each side.
+        files.dedup_by(|a, b| a.path == b.path);
+        Ok(files)
+    }
+
+    /// Returns the commits reachable from HEAD, newest first.
+    pub fn log(&self, options: &LogOptions) -> Result<Vec<LogEntry>> {
+        let repo = &self.repo;
+        repo.head()?;
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push_head()?;
+        revwalk.set_sorting(Sort::TIME)?;
+
+        let mut entries = Vec::new();
+        for id in revwalk {
+            if options.count.is_some_and(|count| entries.len() >= count) {
+                break;
+            }
+            let commit = repo.find_commit(id?)?;
+            let time = commit.time().seconds();
+            if options.since.is_some_and(|since| time < since)
+                || options.until.is_some_and(|until| time > until)
+                || !self.commit_touches(&commit, &options.paths)?
+            {
+                continue;
+            }
+            entries.push(LogEntry::from(&commit));
+        }
+        Ok(entries)
+    }
+
+    /// Returns true if `commit` changes any of `paths` relative to its first parent.
+    fn commit_touches(&self, commit: &Commit, paths: &[String]) -> Result<bool> {
+        if paths.is_empty() {
+            return Ok(true);
+        }
+        let mut options = DiffOptions::new();
+        for i in paths {
+            options.pathspec(i);
+        }
+        let parent_tree = commit.parent(0).ok().and_then(|parent| parent.tree().ok());
+        let diff = self.repo.diff_tree_to_tree(
+            parent_tree.as_ref(),
+            Some(&commit.tree()?),
+            Some(&mut options),
+        )?;
+        Ok(diff.deltas().len() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A new home repo in a temporary home directory, with a git identity.
+    fn new_home() -> (TempDir, GitHome) {
+        let dir = tempfile::tempdir().unwrap();
+        let env = Environment {
+            user: String::from("me"),
+            ..Environment::default()
+        };
+        let home = GitHome::init(dir.path().join(".config/git_home"), dir.path(), env).unwrap();
+        let mut config = home.repo().config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        (dir, home)
+    }
+
+    fn write(dir: &TempDir, path: &str, contents: &str) {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn paths(entries: &[StatusEntry]) -> Vec<(&str, ChangeKind)> {
+        entries.iter().map(|x| (x.path.as_str(), x.kind)).collect()
+    }
+
+    #[test]
+    fn opens_an_initialized_repo() {
+        let (dir, home) = new_home();
+        assert_eq!(home.home_dir(), dir.path());
+        drop(home);
+
+        let env = Environment {
+            user: String::from("me"),
+            ..Environment::default()
+        };
+        let home = GitHome::open(dir.path().join(".config/git_home"), dir.path(), env).unwrap();
+        assert_eq!(home.home_dir(), dir.path());
+        assert_eq!(home.env().user, "me");
+        assert_eq!(home.into_repo().workdir(), Some(dir.path()));
+        assert!(GitHome::open(
+            dir.path().join("missing"),
+            dir.path(),
+            Environment::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn adds_and_commits_files() {
+        let (dir, home) = new_home();
+        write(&dir, ".bashrc", "alias ls='ls -F'\n");
+        write(&dir, ".config/app/a.conf", "a\n");
+        write(&dir, ".config/app/b.conf", "b\n");
+
+        let mut staged = home
+            .add(
+                &[String::from(".bashrc"), String::from(".config/app")],
+                false,
+            )
+            .unwrap();
+        staged.sort();
+        assert_eq!(
+            staged,
+            [".bashrc", ".config/app/a.conf", ".config/app/b.conf"]
+        );
+        let status = home.status().unwrap();
+        assert_eq!(status.branch.as_deref(), Some("master"));
+        assert!(status.upstream.is_none());
+        assert_eq!(
+            paths(&status.staged),
+            [
+                (".bashrc", ChangeKind::New),
+                (".config/app/a.conf", ChangeKind::New),
+                (".config/app/b.conf", ChangeKind::New),
+            ]
+        );
+        assert!(status.unstaged.is_empty());
+
+        let id = home.commit("Track .bashrc").unwrap();
+        assert_eq!(home.repo().head().unwrap().target(), Some(id));
+        assert!(home.status().unwrap().staged.is_empty());
+    }
+
+    #[test]
+    fn adding_a_directory_skips_the_repo_in_it() {
+        let (dir, home) = new_home();
+        write(&dir, ".config/app/a.conf", "a\n");
+
+        let staged = home.add(&[String::from(".config")], false).unwrap();
+        assert_eq!(staged, [".config/app/a.conf"]);
+    }
+
+    #[test]
+    fn refuses_empty_commits() {
+        let (dir, home) = new_home();
+        assert!(matches!(
+            home.commit("Nothing"),
+            Err(Error::NothingToCommit)
+        ));
+
+        write(&dir, ".bashrc", "\n");
+        home.add(&[String::from(".bashrc")], false).unwrap();
+        assert!(matches!(home.commit("  \n"), Err(Error::EmptyMessage)));
+        home.commit("Track .bashrc").unwrap();
+        assert!(matches!(home.commit("Again"), Err(Error::NothingToCommit)));
+    }
+
+    #[test]
+    fn refuses_to_add_ignored_files_without_force() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir, IGNORE_FILE, "*.log\n");
+        write(&dir, "debug.log", "\n");
+        let home = GitHome::init(
+            dir.path().join(".config/git_home"),
+            dir.path(),
+            Environment::default(),
+        )
+        .unwrap();
+
+        match home.add(&[String::from("debug.log")], false) {
+            Err(Error::Ignored(paths)) => assert_eq!(paths, ["debug.log"]),
+            other => panic!("expected Error::Ignored, got {:?}", other),
+        }
+        assert_eq!(
+            home.add(&[String::from("debug.log")], true).unwrap(),
+            ["debug.log"]
+        );
+    }
+
+    #[test]
+    fn add_updated_stages_changes_and_deletions() {
+        let (dir, home) = new_home();
+        write(&dir, ".bashrc", "one\n");
+        write(&dir, ".profile", "one\n");
+        home.add(&[String::from(".bashrc"), String::from(".profile")], false)
+            .unwrap();
+        home.commit("Track dotfiles").unwrap();
+
+        write(&dir, ".bashrc", "two\n");
+        fs::remove_file(dir.path().join(".profile")).unwrap();
+        assert_eq!(
+            paths(&home.status().unwrap().unstaged),
+            [
+                (".bashrc", ChangeKind::Modified),
+                (".profile", ChangeKind::Deleted)
+            ]
+        );
+
+        assert_eq!(home.add_updated().unwrap(), [".bashrc", ".profile"]);
+        let status = home.status().unwrap();
+        assert_eq!(
+            paths(&status.staged),
+            [
+                (".bashrc", ChangeKind::Modified),
+                (".profile", ChangeKind::Deleted)
+            ]
+        );
+        assert!(status.unstaged.is_empty());
+        assert!(home.add_updated().unwrap().is_empty());
+    }
+
+    #[test]
+    fn lists_untracked_files_next_to_tracked_ones() {
+        let (dir, home) = new_home();
+        write(&dir, ".config/app/a.conf", "a\n");
+        write(&dir, ".config/app/b.conf", "b\n");
+        write(&dir, ".config/other/c.conf", "c\n");
+        home.add(&[String::from(".config/app/a.conf")], false)
+            .unwrap();
+
+        assert_eq!(home.untracked().unwrap(), [".config/app/b.conf"]);
+    }
+
+    #[test]
+    fn lists_tracked_files_in_the_index_and_commits() {
+        let (dir, home) = new_home();
+        write(&dir, ".profile", "12345\n");
+        write(&dir, ".bashrc", "1\n");
+        home.add(&[String::from(".profile"), String::from(".bashrc")], false)
+            .unwrap();
+        let id = home.commit("Track dotfiles").unwrap();
+        write(&dir, ".vimrc", "\n");
+        home.add(&[String::from(".vimrc")], false).unwrap();
+
+        let tracked = |tree: Option<&Tree>| -> Vec<(String, u32, usize)> {
+            home.tracked(tree)
+                .unwrap()
+                .into_iter()
+                .map(|x| (x.path, x.mode, x.size))
+                .collect()
+        };
+        assert_eq!(
+            tracked(None),
+            [
+                (String::from(".bashrc"), 0o100644, 2),
+                (String::from(".profile"), 0o100644, 6),
+                (String::from(".vimrc"), 0o100644, 1),
+            ]
+        );
+        let tree = home.repo().find_commit(id).unwrap().tree().unwrap();
+        assert_eq!(
+            tracked(Some(&tree)),
+            [
+                (String::from(".bashrc"), 0o100644, 2),
+                (String::from(".profile"), 0o100644, 6)
+            ]
+        );
+    }
+
+    #[test]
+    fn logs_commits_reachable_from_head() {
+        let (dir, home) = new_home();
+        assert!(home.log(&LogOptions::default()).is_err());
+        for (path, message) in [
+            (".bashrc", "First"),
+            (".profile", "Second\n\nBody"),
+            (".bashrc", "Third"),
+        ] {
+            write(&dir, path, message);
+            home.add(&[String::from(path)], false).unwrap();
+            home.commit(message).unwrap();
+        }
+
+        let summaries = |options: &LogOptions| -> Vec<String> {
+            home.log(options)
+                .unwrap()
+                .iter()
+                .map(|x| String::from(x.summary()))
+                .collect()
+        };
+        let log = home.log(&LogOptions::default()).unwrap();
+        assert_eq!(log.len(), 3);
+        assert!(log
+            .iter()
+            .all(|x| x.author == "Test" && x.email == "test@example.com"));
+        // The commits share a timestamp, so their order is not checked.
+        let second = log.iter().find(|x| x.summary() == "Second").unwrap();
+        assert_eq!(second.message, "Second\n\nBody");
+        assert_eq!(
+            summaries(&LogOptions {
+                count: Some(2),
+                ..LogOptions::default()
+            })
+            .len(),
+            2
+        );
+        assert_eq!(
+            summaries(&LogOptions {
+                paths: vec![String::from(".profile")],
+                ..LogOptions::default()
+            }),
+            ["Second"]
+        );
+        assert!(summaries(&LogOptions {
+            since: Some(second.time + 1),
+            ..LogOptions::default()
+        })
+        .is_empty());
+        assert_eq!(
+            summaries(&LogOptions {
+                until: Some(second.time),
+                ..LogOptions::default()
+            })
+            .len(),
+            3
+        );
+    }
+}
@@ -0,0 +1,224 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Ignored files.
+//!
+//! `$HOME/.githomeignore` holds gitignore patterns, relative to $HOME, for
+//! files git home should never pick up. The patterns are handed to libgit2
+//! when the home repo is opened, so they apply everywhere it looks for files.
+
+use git2::Repository;
+use std::{fs, io, path::Path};
+
+/// The tracked file listing the ignore patterns, relative to $HOME.
+pub const IGNORE_FILE: &str = ".githomeignore";
+
+/// Returns the lines of the ignore file, or nothing if there is none.
+pub fn get_ignore_lines(home_dir: &Path) -> Vec<String> {
+    match fs::read_to_string(home_dir.join(IGNORE_FILE)) {
+        Ok(contents) => contents.lines().map(String::from).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Returns the patterns in the ignore file, skipping comments and blank lines.
+pub fn get_ignore_patterns(home_dir: &Path) -> Vec<String> {
+    get_ignore_lines(home_dir)
+        .into_iter()
+        .filter(|x| !x.trim().is_empty() && !x.starts_with('#'))
+        .collect()
+}
+
+/// Overwrites the ignore file with `lines`.
+pub fn set_ignore_lines(home_dir: &Path, lines: &[String]) -> io::Result<()> {
+    let mut contents = String::new();
+    for i in lines {
+        contents.push_str(i);
+        contents.push('\n');
+    }
+    fs::write(home_dir.join(IGNORE_FILE), contents)
+}
+
+/// Makes the repo ignore the files matching the patterns in the ignore file.
+pub fn load_ignore_rules(repo: &Repository, home_dir: &Path) -> Result<(), git2::Error> {
+    let patterns = get_ignore_patterns(home_dir);
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    repo.add_ignore_rule(&patterns.join("\n"))
+}
+
+/// Returns true if the file at `path`, relative to $HOME, is ignored.
+pub fn is_ignored(repo: &Repository, path: &str) -> bool {
+    repo.is_path_ignored(path).unwrap_or(false)
+}
+
+/// Why a path is or is not ignored.
+#[derive(Debug, PartialEq)]
+pub enum IgnoreReason {
+    /// Ignored by the pattern on the given line of the ignore file.
+    Pattern(usize, String),
+    /// Matched by an ignore pattern, but brought back by the negated pattern
+    /// on the given line.
+    Negated(usize, String),
+    /// Ignored by a .gitignore file or git's exclude settings.
+    Git,
+    NotIgnored,
+}
+
+/// Finds the rule deciding whether `path` is ignored.
+///
+/// As in gitignore the last matching pattern wins, so each pattern is
+/// checked on its own and the last one that matches is the answer. The
+/// patterns are tried on a handle of its own, leaving the rules of `repo`
+/// as they are.
+pub fn explain_ignore(repo: &Repository, home_dir: &Path, path: &str) -> IgnoreReason {
+    let ignored = is_ignored(repo, path);
+    let scratch = Repository::open_bare(repo.path())
+        .and_then(|scratch| scratch.set_workdir(home_dir, false).map(|_| scratch));
+    let scratch = match scratch {
+        // Without any of the patterns, only git's own rules apply.
+        Ok(scratch) if !is_ignored(&scratch, path) => scratch,
+        _ => {
+            return match ignored {
+                true => IgnoreReason::Git,
+                false => IgnoreReason::NotIgnored,
+            }
+        }
+    };
+    let mut matches = Vec::new();
+    for (line, pattern) in get_ignore_lines(home_dir).into_iter().enumerate() {
+        if pattern.trim().is_empty() || pattern.starts_with('#') {
+            continue;
+        }
+        let rule = pattern.strip_prefix('!').unwrap_or(&pattern);
+        if scratch.add_ignore_rule(rule).is_err() {
+            continue;
+        }
+        let matched = is_ignored(&scratch, path);
+        let _ = scratch.clear_ignore_rules();
+        if matched {
+            matches.push((line + 1, pattern));
+        }
+    }
+
+    // A negated pattern can not bring back a file inside an ignored
+    // directory, so an ignored file is explained by the last plain pattern.
+    let last_plain = matches
+        .iter()
+        .rev()
+        .find(|(_, x)| !x.starts_with('!'))
+        .cloned();
+    match (ignored, matches.pop()) {
+        (false, Some((line, pattern))) if pattern.starts_with('!') => {
+            IgnoreReason::Negated(line, pattern)
+        }
+        (false, _) => IgnoreReason::NotIgnored,
+        (true, _) => match last_plain {
+            Some((line, pattern)) => IgnoreReason::Pattern(line, pattern),
+            None => IgnoreReason::Git,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A home directory with a repo ignoring `lines`.
+    fn ignoring(lines: &[&str]) -> (TempDir, Repository) {
+        let home = tempfile::tempdir().unwrap();
+        let repo = Repository::init_bare(home.path().join(".config/git_home")).unwrap();
+        repo.set_workdir(home.path(), false).unwrap();
+        let lines: Vec<String> = lines.iter().map(|x| String::from(*x)).collect();
+        set_ignore_lines(home.path(), &lines).unwrap();
+        load_ignore_rules(&repo, home.path()).unwrap();
+        (home, repo)
+    }
+
+    #[test]
+    fn explains_the_last_matching_pattern() {
+        let (home, repo) = ignoring(&["# editors", "*.swp", "", ".cache/*.swp"]);
+        assert_eq!(
+            explain_ignore(&repo, home.path(), ".cache/a.swp"),
+            IgnoreReason::Pattern(4, String::from(".cache/*.swp"))
+        );
+        assert_eq!(
+            explain_ignore(&repo, home.path(), "a.swp"),
+            IgnoreReason::Pattern(2, String::from("*.swp"))
+        );
+    }
+
+    #[test]
+    fn explains_negated_patterns() {
+        let (home, repo) = ignoring(&["*.log", "!keep.log"]);
+        assert_eq!(
+            explain_ignore(&repo, home.path(), "keep.log"),
+            IgnoreReason::Negated(2, String::from("!keep.log"))
+        );
+        assert!(!is_ignored(&repo, "keep.log"));
+    }
+
+    #[test]
+    fn negation_does_not_reach_into_ignored_directories() {
+        let (home, repo) = ignoring(&[".cache/", "!.cache/keep"]);
+        assert_eq!(
+            explain_ignore(&repo, home.path(), ".cache/keep"),
+            IgnoreReason::Pattern(1, String::from(".cache/"))
+        );
+    }
+
+    #[test]
+    fn explains_files_that_are_not_ignored() {
+        let (home, repo) = ignoring(&["*.swp"]);
+        assert_eq!(
+            explain_ignore(&repo, home.path(), ".bashrc"),
+            IgnoreReason::NotIgnored
+        );
+    }
+
+    #[test]
+    fn explains_gitignore_files() {
+        let (home, repo) = ignoring(&["*.swp"]);
+        fs::write(home.path().join(".gitignore"), "*.tmp\n").unwrap();
+        assert_eq!(
+            explain_ignore(&repo, home.path(), "a.tmp"),
+            IgnoreReason::Git
+        );
+    }
+
+    #[test]
+    fn keeps_the_rules_loaded() {
+        let (home, repo) = ignoring(&["*.swp"]);
+        explain_ignore(&repo, home.path(), ".bashrc");
+        explain_ignore(&repo, home.path(), "a.tmp");
+        assert!(is_ignored(&repo, "a.swp"));
+    }
+
+    #[test]
+    fn leaves_rules_added_by_the_caller() {
+        let (home, repo) = ignoring(&["*.swp"]);
+        repo.add_ignore_rule("*.bak").unwrap();
+        assert_eq!(
+            explain_ignore(&repo, home.path(), "a.bak"),
+            IgnoreReason::Git
+        );
+        explain_ignore(&repo, home.path(), "a.swp");
+        assert!(is_ignored(&repo, "a.bak"));
+        assert!(is_ignored(&repo, "a.swp"));
+    }
+}
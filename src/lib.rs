@@ -0,0 +1,89 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A dotfiles manager using git.
+//!
+//! The `git home` command is a thin layer over this crate. Start with
+//! [`GitHome`], which opens a home repo from an explicit repo and home path:
+//!
+//! ```no_run
+//! use git_home::{Environment, GitHome};
+//!
+//! let env = Environment { user: String::from("me"), ..Environment::default() };
+//! let home = GitHome::open("/home/me/.config/git_home", "/home/me", env)?;
+//! home.add(&[String::from(".bashrc")], false)?;
+//! home.commit("Track .bashrc")?;
+//! # Ok::<(), git_home::Error>(())
+//! ```
+
+/// The default location of the home repo, relative to $HOME.
+pub const GIT_HOME_DIR: &str = ".config/git_home";
+/// The config file, relative to $HOME.
+pub const GIT_HOME_CONFIG_FILE: &str = ".config/git-home/config.toml";
+/// Where files displaced from $HOME are moved, relative to $HOME.
+pub const GIT_HOME_BACKUP_DIR: &str = ".local/share/git_home/backup";
+/// The untracked file holding this machine's template values, relative to $HOME.
+pub const GIT_HOME_VALUES_FILE: &str = ".config/git_home_values";
+/// The untracked key secrets are encrypted with, relative to $HOME.
+pub const GIT_HOME_KEY_FILE: &str = ".config/git_home_key";
+
+pub mod config;
+pub mod doctor;
+pub mod error;
+pub mod git;
+mod home;
+pub mod ignore;
+pub mod profile;
+pub mod secret;
+pub mod template;
+
+pub use error::{Context, Error, Result};
+use secret::KeyCache;
+
+/// What the library would otherwise take from the environment. It never
+/// reads environment variables or prints itself; `git home` fills this in
+/// from the environment and prints the warnings.
+#[derive(Debug, Clone)]
+pub struct Environment {
+    /// The login name, rendered into the `user` template value.
+    pub user: String,
+    /// Encrypts secrets with this passphrase instead of the key file.
+    pub passphrase: Option<String>,
+    /// The passphrase `secrets rekey` switches to instead of a new key file.
+    pub new_passphrase: Option<String>,
+    /// The profile to use instead of the one matching the hostname.
+    pub profile: Option<String>,
+    /// Called with problems that do not stop what is being done, such as a
+    /// secret that can not be decrypted.
+    pub warn: fn(&str),
+    /// Keys derived from the passphrases, shared by the clones of this
+    /// environment.
+    pub keys: KeyCache,
+}
+
+impl Default for Environment {
+    fn default() -> Environment {
+        Environment {
+            user: String::new(),
+            passphrase: None,
+            new_passphrase: None,
+            profile: None,
+            warn: |_| (),
+            keys: KeyCache::default(),
+        }
+    }
+}
+pub use home::{GitHome, LogEntry, LogOptions, Status, TrackedFile, Upstream};
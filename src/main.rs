@@ -14,32 +14,62 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-// this is a test ocmment
-use std::io;
+use git_home::{Error, Result};
 use std::process::exit;
-const GIT_HOME_DIR: &str = ".config/git_home";
 
 mod args;
-mod git;
+mod cli;
 mod run;
 
 use args::usage::*;
 use args::*;
-use git::*;
+use cli::*;
 use run::*;
 
-fn main() -> io::Result<()> {
-    match format_args()? {
+fn main() {
+    if let Err(e) = run(format_args()) {
+        eprintln!("error: {}", e);
+        exit(e.exit_code());
+    }
+}
+
+/// Runs the command `mode` parsed from the arguments.
+fn run(mode: Result<ProgMode>) -> Result<()> {
+    match mode? {
         ProgMode::Add(args) => run_add(args),
+        ProgMode::Rm(args) => run_rm(args),
+        ProgMode::Diff(args) => run_diff(args),
+        ProgMode::Remote(args) => run_remote(args),
+        ProgMode::Push(args) => run_push(args),
+        ProgMode::Fetch(args) => run_fetch(args),
+        ProgMode::Pull(args) => run_pull(args),
+        ProgMode::Clone(url) => run_clone(url),
+        ProgMode::Show(args) => run_show(args),
+        ProgMode::Restore(args) => run_restore(args),
+        ProgMode::Reset(args) => run_reset(args),
+        ProgMode::Branch(args) => run_branch(args),
+        ProgMode::Switch(args) => run_switch(args),
+        ProgMode::Profile(list) => run_profile(list),
+        ProgMode::Template(args) => run_template(args),
+        ProgMode::Secrets(args) => run_secrets(args),
+        ProgMode::Ignore(args) => run_ignore(args),
+        ProgMode::Config(args) => run_config(args),
+        ProgMode::Doctor => run_doctor(),
+        ProgMode::Completions(args) => run_completions(args),
+        ProgMode::Manpage => run_manpage(),
+        ProgMode::LsFiles(args) => run_ls_files(args),
         ProgMode::Init => run_init(),
-        ProgMode::Status(color) => print_repo_status(color),  
-        ProgMode::Commit(args) => run_commit(args.values),
-        ProgMode::Log => run_log(),
-        ProgMode::Help => print_usage(),
-	ProgMode::Passthrough(prefix_args, pass_throug_args) => run_passthrough(prefix_args, pass_throug_args),
+        ProgMode::Status(args) => print_repo_status(args),
+        ProgMode::Commit(args) => run_commit(args),
+        ProgMode::Log(args) => run_log(args),
+        ProgMode::Help(command) => Ok(print_help(command)?),
+        ProgMode::Version => Ok(print_version()?),
+        ProgMode::Passthrough(prefix_args, pass_throug_args) => {
+            exit(run_passthrough(prefix_args, pass_throug_args)?)
+        }
         ProgMode::None => {
             print_usage()?;
-            exit(64);
+            Err(Error::Usage(String::from("no command given")))
         }
     }
 }
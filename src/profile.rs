@@ -0,0 +1,342 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Host profiles.
+//!
+//! Profiles live in the home repo's git config:
+//!
+//! ```text
+//! [profile "laptop"]
+//!     host = laptop-*
+//!     branch = laptop
+//!     overlay = .config/hosts/laptop
+//! ```
+//!
+//! `host` may be given more than once and accepts `*` and `?` wildcards.
+//!
+//! Tracked files in the overlay directory replace the file at the same path
+//! relative to $HOME, so `.config/hosts/laptop/.bashrc` is what the laptop
+//! gets as `.bashrc`.
+
+use super::Environment;
+use git2::Repository;
+use std::fmt;
+
+/// A set of settings that apply to the machines matching its hosts.
+#[derive(Debug, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub hosts: Vec<String>,
+    /// The branch this host is expected to have checked out.
+    pub branch: Option<String>,
+    /// The directory, relative to $HOME, holding files specific to this host.
+    pub overlay: Option<String>,
+}
+
+/// How the active profile was chosen.
+#[derive(Debug, PartialEq)]
+pub enum Resolution {
+    /// Named by [`Environment::profile`], which `git home` takes from the
+    /// GIT_HOME_PROFILE environment variable.
+    Env,
+    /// A `host` entry equal to the hostname.
+    Host(String),
+    /// A `host` entry with wildcards matching the hostname.
+    Pattern(String),
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Resolution::Env => write!(f, "set by $GIT_HOME_PROFILE"),
+            Resolution::Host(host) => write!(f, "matched host '{}'", host),
+            Resolution::Pattern(pattern) => write!(f, "matched host pattern '{}'", pattern),
+        }
+    }
+}
+
+/// Returns the name of the machine git home is running on.
+pub fn get_hostname() -> String {
+    gethostname::gethostname().to_string_lossy().into_owned()
+}
+
+/// Returns true if `text` matches `pattern`, where `*` matches any run of
+/// characters and `?` matches exactly one.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Reads every profile from the home repo's config, in the order they were
+/// first defined.
+pub fn get_profiles(repo: &Repository) -> Vec<Profile> {
+    let mut profiles: Vec<Profile> = Vec::new();
+    let config = match repo.config() {
+        Ok(config) => config,
+        Err(_) => return profiles,
+    };
+    let entries = match config.entries(Some("profile\\..*")) {
+        Ok(entries) => entries,
+        Err(_) => return profiles,
+    };
+    for entry in &entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let (name, value) = match (entry.name(), entry.value()) {
+            (Some(name), Some(value)) => (name, String::from(value)),
+            _ => continue,
+        };
+        let (profile_name, key) = match name
+            .strip_prefix("profile.")
+            .and_then(|rest| rest.rsplit_once('.'))
+        {
+            Some(split) => split,
+            None => continue,
+        };
+
+        let index = match profiles.iter().position(|x| x.name == profile_name) {
+            Some(index) => index,
+            None => {
+                profiles.push(Profile {
+                    name: String::from(profile_name),
+                    hosts: Vec::new(),
+                    branch: None,
+                    overlay: None,
+                });
+                profiles.len() - 1
+            }
+        };
+        let profile = &mut profiles[index];
+        match key {
+            "host" => profile.hosts.push(value),
+            "branch" => profile.branch = Some(value),
+            "overlay" => profile.overlay = Some(value),
+            _ => (),
+        }
+    }
+    profiles
+}
+
+/// Finds the profile for `hostname`.
+///
+/// The profile named by `env.profile` wins if set, then an exact host
+/// match, then the first matching host pattern.
+pub fn resolve_profile(
+    repo: &Repository,
+    hostname: &str,
+    env: &Environment,
+) -> Option<(Profile, Resolution)> {
+    let profiles = get_profiles(repo);
+
+    if let Some(name) = &env.profile {
+        return profiles
+            .into_iter()
+            .find(|x| &x.name == name)
+            .map(|profile| (profile, Resolution::Env));
+    }
+
+    let exact = profiles
+        .iter()
+        .position(|x| x.hosts.iter().any(|host| host == hostname));
+    if let Some(index) = exact {
+        let profile = profiles.into_iter().nth(index)?;
+        return Some((profile, Resolution::Host(String::from(hostname))));
+    }
+
+    profiles.into_iter().find_map(|profile| {
+        let pattern = profile
+            .hosts
+            .iter()
+            .find(|host| wildcard_match(host, hostname))
+            .cloned()?;
+        Some((profile, Resolution::Pattern(pattern)))
+    })
+}
+
+/// Returns the overlay directory of the profile for this host, relative to
+/// $HOME and without a trailing slash.
+pub fn get_overlay_dir(repo: &Repository, env: &Environment) -> Option<String> {
+    let (profile, _) = resolve_profile(repo, &get_hostname(), env)?;
+    let dir = profile.overlay?;
+    let dir = dir.trim_matches('/');
+    match dir.is_empty() {
+        true => None,
+        false => Some(String::from(dir)),
+    }
+}
+
+/// Returns the path in $HOME that `path`, a file in the overlay directory
+/// `overlay`, replaces.
+pub fn strip_overlay<'a>(overlay: &str, path: &'a str) -> Option<&'a str> {
+    path.strip_prefix(overlay)?.strip_prefix('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A repo with `entries` set in its config.
+    fn configured(entries: &[(&str, &str)]) -> (TempDir, Repository) {
+        let home = tempfile::tempdir().unwrap();
+        let repo = Repository::init_bare(home.path().join(".config/git_home")).unwrap();
+        let mut config = repo.config().unwrap();
+        for (name, value) in entries {
+            config.set_multivar(name, "^$", value).unwrap();
+        }
+        (home, repo)
+    }
+
+    fn profile_name(found: Option<(Profile, Resolution)>) -> Option<(String, Resolution)> {
+        found.map(|(profile, resolution)| (profile.name, resolution))
+    }
+
+    #[test]
+    fn wildcards_match() {
+        assert!(wildcard_match("laptop", "laptop"));
+        assert!(wildcard_match("laptop-*", "laptop-"));
+        assert!(wildcard_match("laptop-*", "laptop-work"));
+        assert!(wildcard_match("*-work", "laptop-work"));
+        assert!(wildcard_match("l?ptop", "laptop"));
+        assert!(wildcard_match("*a*a*", "banana"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("laptop", "laptop-work"));
+        assert!(!wildcard_match("laptop-*", "desktop-work"));
+        assert!(!wildcard_match("l?ptop", "lptop"));
+        assert!(!wildcard_match("*a*a*", "band"));
+        assert!(!wildcard_match("?", ""));
+    }
+
+    #[test]
+    fn reads_profiles_in_order() {
+        let (_home, repo) = configured(&[
+            ("profile.laptop.host", "laptop-*"),
+            ("profile.server.host", "web1"),
+            ("profile.laptop.host", "travel"),
+            ("profile.laptop.branch", "laptop"),
+            ("profile.laptop.overlay", ".config/hosts/laptop"),
+        ]);
+        assert_eq!(
+            get_profiles(&repo),
+            vec![
+                Profile {
+                    name: String::from("laptop"),
+                    hosts: vec![String::from("laptop-*"), String::from("travel")],
+                    branch: Some(String::from("laptop")),
+                    overlay: Some(String::from(".config/hosts/laptop")),
+                },
+                Profile {
+                    name: String::from("server"),
+                    hosts: vec![String::from("web1")],
+                    branch: None,
+                    overlay: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn exact_host_wins_over_pattern() {
+        let (_home, repo) =
+            configured(&[("profile.any.host", "web*"), ("profile.web1.host", "web1")]);
+        let env = Environment::default();
+        assert_eq!(
+            profile_name(resolve_profile(&repo, "web1", &env)),
+            Some((String::from("web1"), Resolution::Host(String::from("web1"))))
+        );
+        assert_eq!(
+            profile_name(resolve_profile(&repo, "web2", &env)),
+            Some((
+                String::from("any"),
+                Resolution::Pattern(String::from("web*"))
+            ))
+        );
+        assert_eq!(profile_name(resolve_profile(&repo, "db1", &env)), None);
+    }
+
+    #[test]
+    fn first_matching_pattern_wins() {
+        let (_home, repo) = configured(&[
+            ("profile.first.host", "*-work"),
+            ("profile.second.host", "laptop-*"),
+        ]);
+        let found = profile_name(resolve_profile(
+            &repo,
+            "laptop-work",
+            &Environment::default(),
+        ));
+        assert_eq!(
+            found,
+            Some((
+                String::from("first"),
+                Resolution::Pattern(String::from("*-work"))
+            ))
+        );
+    }
+
+    #[test]
+    fn environment_profile_wins_over_host() {
+        let (_home, repo) = configured(&[
+            ("profile.web1.host", "web1"),
+            ("profile.other.host", "other"),
+        ]);
+        let env = Environment {
+            profile: Some(String::from("other")),
+            ..Environment::default()
+        };
+        assert_eq!(
+            profile_name(resolve_profile(&repo, "web1", &env)),
+            Some((String::from("other"), Resolution::Env))
+        );
+
+        let env = Environment {
+            profile: Some(String::from("missing")),
+            ..Environment::default()
+        };
+        assert_eq!(profile_name(resolve_profile(&repo, "web1", &env)), None);
+    }
+
+    #[test]
+    fn strips_the_overlay_directory() {
+        assert_eq!(strip_overlay("hosts/a", "hosts/a/.bashrc"), Some(".bashrc"));
+        assert_eq!(
+            strip_overlay("hosts/a", "hosts/a/.config/x"),
+            Some(".config/x")
+        );
+        assert_eq!(strip_overlay("hosts/a", "hosts/ab/.bashrc"), None);
+        assert_eq!(strip_overlay("hosts/a", ".bashrc"), None);
+    }
+}
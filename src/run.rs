@@ -14,247 +14,1420 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use super::{args::*, git::*, usage::*, GIT_HOME_DIR};
-use chrono::{Local, TimeZone};
-use git2::{Repository, StatusOptions};
-use crate::args::ProgMode;
+use crate::{
+    args::completions::gen_completions, args::manpage::gen_manpage, args::usage::*, args::*, cli::*,
+};
+use git2::{
+    build::{CheckoutBuilder, RepoBuilder},
+    BranchType, Config, DiffOptions, FetchOptions, ObjectType, PushOptions, Repository, ResetType,
+    Tree, TreeWalkMode, TreeWalkResult,
+};
+use git_home::config::{find_setting, Source, SETTINGS};
+use git_home::doctor::{diagnose, Outcome};
+use git_home::git::*;
+use git_home::ignore::{
+    explain_ignore, get_ignore_lines, set_ignore_lines, IgnoreReason, IGNORE_FILE,
+};
+use git_home::profile::{get_hostname, get_profiles, resolve_profile};
+use git_home::secret::{
+    get_secret_patterns, is_encrypted, matches_secret, set_secret_patterns, Key, SECRETS_FILE,
+};
+use git_home::template::{get_template_paths, is_template, set_template_paths, TEMPLATES_FILE};
+use git_home::{
+    Context, Environment, Error, GitHome, LogEntry, LogOptions, Result, GIT_HOME_KEY_FILE,
+};
 use std::boxed::Box;
-use std::{env, io::{self, Write, Read}, path::Path, process::{exit, Command, Stdio}};
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
 /// Runs the program in add mode.
-pub fn run_add(args: AddArgs) -> io::Result<()> {
+pub fn run_add(args: AddArgs) -> Result<()> {
+    let home = open_git_home()?;
+    match args.mode {
+        AddMode::All => home.add_updated()?,
+        AddMode::Normal => home.add(&args.values, args.force)?,
+    };
+    Ok(())
+}
+
+/// Runs the program in rm mode.
+pub fn run_rm(args: RmArgs) -> Result<()> {
     let repo = open_home_repo()?;
-    let mut index = match repo.index() {
-        Ok(index) => index,
-        Err(e) => {
-            eprintln!("Could not open index: {}", e);
-            exit(74);
+    let mut index = repo.index().context("Could not open index")?;
+
+    let mut removed = Vec::new();
+    for i in args.values.iter() {
+        let mut matched = false;
+        index
+            .remove_all(
+                [i],
+                Some(&mut |path: &Path, _: &[u8]| {
+                    matched = true;
+                    removed.push(path.to_path_buf());
+                    0
+                }),
+            )
+            .context("index error")?;
+        if !matched {
+            return Err(Error::NotFound(format!(
+                "$HOME/{} is not tracked by git home",
+                i
+            )));
         }
-    };
+    }
+    index.write().context("could not write to index")?;
 
-    match args.mode {
-        AddMode::All => {
-            let mut files_to_update = Vec::new();
-            let mut options = StatusOptions::new();
-            options.include_untracked(false);
-            options.show(git2::StatusShow::Workdir);
-            let status = match repo.statuses(Some(&mut options)) {
-                Ok(status) => status,
-                Err(err) => {
-                    eprintln!("Could not get repo status: {}", err);
-                    exit(74);
-                }
-            };
-            if status.len() > 0 {
-                for i in status.iter() {
-                    files_to_update.push(match i.path() {
-                        Some(path) => {
-                            let path = Path::new(path);
-                            if let Err(e) = index.add_path(path) {
-                                eprintln!("index error: {}", e);
-                                exit(74);
-                            }
-                        }
-                        None => {
-                            eprintln!("Path is not valid utf-8");
-                            exit(1);
-                        }
-                    })
-                }
-                if let Err(e) = index.write() {
-                    eprintln!("could not write to index: {}", e);
-                    exit(74);
+    let home_dir = repo
+        .workdir()
+        .unwrap_or_else(|| Path::new(""))
+        .to_path_buf();
+    for path in removed {
+        if args.mode == RmMode::Delete {
+            if let Err(e) = fs::remove_file(home_dir.join(&path)) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    return Err(e).context(format!("Could not delete $HOME/{}", path.display()));
                 }
             }
         }
-        AddMode::Normal => {
-            let files: Vec<&Path> = args.values.iter().map(|x| Path::new(x)).collect();
-            for i in files {
-                if let Err(e) = index.add_path(i) {
-                    eprintln!("index error: {}", e);
-                    exit(74);
-                }
-            }
-            if let Err(e) = index.write() {
-                eprintln!("could not write to index: {}", e);
-                exit(74);
+        println!("rm '$HOME/{}'", path.display());
+    }
+
+    Ok(())
+}
+
+/// Looks up the tree of the commit `rev` points to.
+fn find_rev_tree<'a>(repo: &'a Repository, rev: &str) -> Result<Tree<'a>> {
+    let object = repo
+        .revparse_single(rev)
+        .map_err(|e| Error::NotFound(format!("Unknown revision {}: {}", rev, e.message())))?;
+    object.peel_to_tree().map_err(|e| {
+        Error::NotFound(format!(
+            "{} does not point to a commit: {}",
+            rev,
+            e.message()
+        ))
+    })
+}
+
+/// Runs the program in diff mode.
+pub fn run_diff(args: DiffArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let mut options = DiffOptions::new();
+    options.old_prefix("$HOME/").new_prefix("$HOME/");
+    for i in args.paths.iter() {
+        options.pathspec(i);
+    }
+
+    let workdir = matches!(args.mode, DiffMode::Workdir | DiffMode::Commits(_, None));
+    let diff = match args.mode {
+        DiffMode::Workdir => repo.diff_index_to_workdir(None, Some(&mut options)),
+        DiffMode::Staged => {
+            // There is nothing to compare against before the first commit.
+            let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+            repo.diff_tree_to_index(head.as_ref(), None, Some(&mut options))
+        }
+        DiffMode::Commits(from, None) => {
+            let from = find_rev_tree(&repo, &from)?;
+            repo.diff_tree_to_workdir_with_index(Some(&from), Some(&mut options))
+        }
+        DiffMode::Commits(from, Some(to)) => {
+            let from = find_rev_tree(&repo, &from)?;
+            let to = find_rev_tree(&repo, &to)?;
+            repo.diff_tree_to_tree(Some(&from), Some(&to), Some(&mut options))
+        }
+    };
+    let diff = diff.context("Could not generate diff")?;
+
+    print_diff(&repo, &diff, workdir, args.color)
+}
+
+/// Runs the program in remote mode.
+pub fn run_remote(args: RemoteArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let result = match args.mode {
+        RemoteMode::List => {
+            let names = repo.remotes().context("Could not list remotes")?;
+            for name in names.iter().flatten() {
+                let remote = repo
+                    .find_remote(name)
+                    .context(format!("Could not look up remote {}", name))?;
+                let url = remote.url().unwrap_or("");
+                let push_url = remote.pushurl().unwrap_or(url);
+                println!("{name}\t{url} (fetch)");
+                println!("{name}\t{push_url} (push)");
             }
+            Ok(())
         }
+        RemoteMode::Add(name, url) => repo.remote(&name, &url).map(|_| ()),
+        RemoteMode::Remove(name) => repo.remote_delete(&name),
+        RemoteMode::SetUrl(name, url, push) => match repo.find_remote(&name) {
+            Ok(_) if push => repo.remote_set_pushurl(&name, Some(&url)),
+            Ok(_) => repo.remote_set_url(&name, &url),
+            Err(e) => Err(e),
+        },
     };
 
+    result.context("remote error")
+}
+
+/// Looks up the remote called `remote_name`.
+fn find_remote<'a>(repo: &'a Repository, remote_name: &str) -> Result<git2::Remote<'a>> {
+    repo.find_remote(remote_name).map_err(|e| {
+        Error::NotFound(format!(
+            "Could not find remote {}: {}",
+            remote_name,
+            e.message()
+        ))
+    })
+}
+
+/// Runs the program in push mode.
+pub fn run_push(args: PushArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    if repo.revparse_ext("HEAD").is_err() {
+        return Err(Error::Refused(String::from(
+            "Nothing to push, commit something first.",
+        )));
+    }
+    let head_branch = match current_branch(&repo) {
+        Some(branch) => branch,
+        None => {
+            return Err(Error::Refused(String::from(
+                "HEAD is detached, cannot push.",
+            )))
+        }
+    };
+    let branch = args.branch.unwrap_or(head_branch);
+
+    let has_upstream = get_repo_config(&repo)?
+        .get_string(&format!("branch.{branch}.remote"))
+        .is_ok();
+    let remote_name = resolve_remote_name(&repo, &branch, args.remote)?;
+    let mut remote = find_remote(&repo, &remote_name)?;
+
+    let home_dir = repo.workdir().unwrap_or_else(|| Path::new(""));
+    let mut callbacks = gen_remote_callbacks(get_repo_config(&repo)?, home_dir, print_progress);
+    callbacks.push_update_reference(|refname, status| match status {
+        Some(message) => Err(git2::Error::from_str(&format!(
+            "remote rejected {refname}: {message}"
+        ))),
+        None => Ok(()),
+    });
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+    let refspec = format!("refs/heads/{branch}:refs/heads/{branch}");
+    remote
+        .push(&[&refspec], Some(&mut options))
+        .context(format!("Could not push to {}", remote_name))?;
+    println!("Pushed {branch} to {remote_name}.");
+
+    if !has_upstream {
+        set_branch_upstream(&repo, &branch, &remote_name)?;
+        println!("Branch '{branch}' set up to track '{remote_name}/{branch}'.");
+    }
     Ok(())
 }
 
-/// Commits current index to HEAD.
-pub fn run_initial_commit(args: &Vec<String>) -> io::Result<()> {
-    let repo = match open_home_repo() {
-        Ok(repo) => repo,
-        Err(e) => {
-            eprintln!("Unable to open repo: {}", e);
-            exit(74);
+/// Returns the remote `branch` pushes to and pulls from, falling back to the
+/// remote setting.
+fn resolve_remote_name(repo: &Repository, branch: &str, remote: Option<String>) -> Result<String> {
+    if let Some(remote) = remote {
+        return Ok(remote);
+    }
+    if let Ok(remote) = get_repo_config(repo)?.get_string(&format!("branch.{branch}.remote")) {
+        return Ok(remote);
+    }
+    // The remote setting has a default, so it is always set.
+    Ok(load_config()?
+        .get("remote")
+        .map(|(remote, _)| remote)
+        .unwrap_or_default())
+}
+
+/// Fetches the configured refspecs of `remote_name`.
+fn fetch_remote(repo: &Repository, remote_name: &str) -> Result<()> {
+    let mut remote = find_remote(repo, remote_name)?;
+    let mut options = FetchOptions::new();
+    let home_dir = repo.workdir().unwrap_or_else(|| Path::new(""));
+    options.remote_callbacks(gen_remote_callbacks(
+        get_repo_config(repo)?,
+        home_dir,
+        print_progress,
+    ));
+    remote
+        .fetch::<&str>(&[], Some(&mut options), None)
+        .context(format!("Could not fetch from {}", remote_name))
+}
+
+/// Runs the program in fetch mode.
+pub fn run_fetch(args: FetchArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let branch = current_branch(&repo).unwrap_or_default();
+    let remote_name = resolve_remote_name(&repo, &branch, args.remote)?;
+    fetch_remote(&repo, &remote_name)
+}
+
+/// Runs the program in pull mode.
+///
+/// Nothing in $HOME is touched if the pull would overwrite local changes or
+/// the merge has conflicts.
+pub fn run_pull(args: PullArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let env = get_environment();
+    let branch = match current_branch(&repo) {
+        Some(branch) => branch,
+        None => {
+            return Err(Error::Refused(String::from(
+                "HEAD is detached, cannot pull.",
+            )))
         }
     };
+    let remote_name = resolve_remote_name(&repo, &branch, args.remote)?;
+    let remote_branch = match args.branch {
+        Some(remote_branch) => remote_branch,
+        None => get_repo_config(&repo)?
+            .get_string(&format!("branch.{branch}.merge"))
+            .ok()
+            .and_then(|merge| merge.strip_prefix("refs/heads/").map(String::from))
+            .unwrap_or_else(|| branch.clone()),
+    };
+    fetch_remote(&repo, &remote_name)?;
 
-    let message: String = String::from(&args[0]);
+    let their_ref = repo
+        .find_reference(&format!("refs/remotes/{remote_name}/{remote_branch}"))
+        .map_err(|_| {
+            Error::NotFound(format!("{}/{} does not exist.", remote_name, remote_branch))
+        })?;
+    let their_commit = their_ref.peel_to_commit().context(format!(
+        "Could not look up {}/{}",
+        remote_name, remote_branch
+    ))?;
+    let annotated = repo
+        .reference_to_annotated_commit(&their_ref)
+        .context(format!(
+            "Could not look up {}/{}",
+            remote_name, remote_branch
+        ))?;
+    let (analysis, _) = repo
+        .merge_analysis(&[&annotated])
+        .context("Could not analyze merge")?;
+    if analysis.is_up_to_date() {
+        println!("Already up to date.");
+        return Ok(());
+    }
 
-    let (sig, tree) = gen_init_comimt_args(&repo)?;
-    let _commit = match repo.commit(Some("HEAD"), &sig, &sig, &message, &tree, &[]) {
-        Ok(id) => id,
-        Err(_err) => {
-            eprintln!("Could not create commit");
-            exit(74);
+    let head_commit = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let head_tree = head_commit.as_ref().and_then(|commit| commit.tree().ok());
+    let fast_forward = analysis.is_fast_forward() || analysis.is_unborn();
+    let tree_result = match &head_commit {
+        Some(head_commit) if !fast_forward => {
+            let mut index = repo
+                .merge_commits(head_commit, &their_commit, None)
+                .context(format!("Could not merge {}/{}", remote_name, remote_branch))?;
+            if index.has_conflicts() {
+                let paths = index
+                    .conflicts()
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter_map(|conflict| conflict.our.or(conflict.their).or(conflict.ancestor))
+                    .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+                    .collect();
+                return Err(Error::Conflict {
+                    message: format!(
+                        "Merging {}/{} has conflicts in:",
+                        remote_name, remote_branch
+                    ),
+                    paths,
+                    hint: String::from("Nothing was changed."),
+                });
+            }
+            index.write_tree_to(&repo).and_then(|id| repo.find_tree(id))
         }
+        _ => their_commit.tree(),
     };
-    Ok(())
+    let tree = tree_result.context("Could not look up merged tree")?;
+
+    let overwritten = find_overwritten_files(&repo, head_tree.as_ref(), &tree, &env)?;
+    if !overwritten.is_empty() {
+        return Err(Error::Conflict {
+            message: String::from("Pulling would overwrite local changes to:"),
+            paths: overwritten,
+            hint: String::from("Commit or move them before pulling."),
+        });
+    }
+    checkout_changed_files(&repo, head_tree.as_ref(), &tree, &env)?;
+
+    let result = match &head_commit {
+        Some(head_commit) if !fast_forward => {
+            let message = format!("Merge branch '{remote_branch}' of {remote_name}");
+            let sig = gen_signature(&repo)?;
+            repo.commit(
+                Some("HEAD"),
+                &sig,
+                &sig,
+                &message,
+                &tree,
+                &[head_commit, &their_commit],
+            )
+            .map(|_| println!("Merged {remote_name}/{remote_branch} into {branch}."))
+        }
+        _ => repo
+            .reference(
+                &format!("refs/heads/{branch}"),
+                their_commit.id(),
+                true,
+                "pull: fast-forward",
+            )
+            .map(|_| println!("Fast-forwarded {branch} to {remote_name}/{remote_branch}.")),
+    };
+    result.context(format!("Could not update {}", branch))
 }
-pub fn run_commit_action(args: &Vec<String>) -> io::Result<()> {
-    let repo = match open_home_repo() {
-        Ok(repo) => repo,
-        Err(e) => {
-            eprintln!("Unable to open repo: {}", e);
-            exit(74);
+
+/// Returns true if `$HOME/path` exists and differs from the blob `id`.
+fn differs_from_blob(
+    repo: &Repository,
+    home_dir: &Path,
+    path: &Path,
+    id: git2::Oid,
+    env: &Environment,
+) -> bool {
+    let full_path = home_dir.join(path);
+    let current = match full_path.symlink_metadata() {
+        Ok(meta) if meta.file_type().is_symlink() => {
+            fs::read_link(&full_path).map(|target| target.into_os_string().into_encoded_bytes())
         }
+        Ok(_) => fs::read(&full_path),
+        Err(_) => return false,
     };
-    let message: String = String::from(&args[0]);
+    let blob = match repo.find_blob(id) {
+        Ok(blob) => blob,
+        Err(_) => return true,
+    };
+    let expected = match gen_home_contents(home_dir, &path.to_string_lossy(), blob.content(), env) {
+        Some(expected) => expected,
+        None => blob.content().to_vec(),
+    };
+    match current {
+        Ok(current) => current != expected,
+        Err(_) => true,
+    }
+}
 
-    let (parent, sig, tree) = gen_commit_args(&repo)?;
-    let parent = match parent.as_commit() {
-        Some(commit) => commit,
-        None => {
-            eprintln!("could not get parent commit");
-            exit(74);
+/// Clones a home repo and checks its files out into $HOME.
+///
+/// Any existing file that would be overwritten is moved into a timestamped
+/// backup directory first.
+pub fn run_clone(url: String) -> Result<()> {
+    let git_home_dir = resolve_git_repo()?;
+    let home_dir = get_home_dir()?;
+    let home_dir = Path::new(&home_dir);
+    let env = get_environment();
+    if Path::new(&git_home_dir).exists() {
+        return Err(Error::Refused(format!("{} already exists.", git_home_dir)));
+    }
+
+    let config = Config::open_default().context("Could not open git config")?;
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(gen_remote_callbacks(config, home_dir, print_progress));
+    let repo = RepoBuilder::new()
+        .bare(true)
+        .fetch_options(options)
+        .clone(&url, Path::new(&git_home_dir))
+        .context(format!("Could not clone {}", url))?;
+    get_repo_config(&repo)?
+        .set_str("status.showUntrackedFiles", "no")
+        .context(format!("Could not configure {}", git_home_dir))?;
+    repo.set_workdir(home_dir, false)
+        .context("Could not set working dir")?;
+
+    let tree = match repo.head().and_then(|head| head.peel_to_tree()) {
+        Ok(tree) => tree,
+        Err(_) => {
+            println!("Cloned an empty repository into {}.", git_home_dir);
+            return Ok(());
         }
     };
+    if let Some(branch) = current_branch(&repo) {
+        set_branch_upstream(&repo, &branch, "origin")?;
+    }
 
-    let _commit = match repo.commit(Some("HEAD"), &sig, &sig, &message, &tree, &[&parent]) {
-        Ok(id) => id,
-        Err(_err) => {
-            eprintln!("Could not create commit");
-            exit(74);
+    let mut conflicts = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            let path = Path::new(root).join(entry.name().unwrap_or_default());
+            if differs_from_blob(&repo, home_dir, &path, entry.id(), &env) {
+                conflicts.push(path);
+            }
+        }
+        TreeWalkResult::Ok
+    })
+    .context("Could not read tree")?;
+    if !conflicts.is_empty() {
+        let backup_dir = gen_backup_dir(home_dir);
+        for path in conflicts.iter() {
+            backup_file(home_dir, path, &backup_dir)?;
         }
+        println!(
+            "Moved {} existing files to {}",
+            conflicts.len(),
+            backup_dir.display()
+        );
+    }
+
+    // Every file that differs has been moved out of the way, so forcing only
+    // rewrites files with identical contents.
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))
+        .context("Could not check out files into $HOME")?;
+    write_home_files(&repo, None, None, &env)?;
+    println!("Cloned {} into {}.", url, git_home_dir);
+    Ok(())
+}
+
+/// Writes `output` through `$PAGER` when stdout is a terminal.
+pub fn print_paged(output: &str) -> io::Result<()> {
+    if !io::stdout().is_terminal() {
+        return io::stdout().write_all(output.as_bytes());
+    }
+    let pager = match load_config().ok().and_then(|config| config.get("pager")) {
+        Some((pager, _)) => pager,
+        None => String::from("less"),
     };
+    let mut command = Command::new("sh");
+    command.args(["-c", &pager]).stdin(Stdio::piped());
+    if env::var("LESS").is_err() {
+        // Quit when the output fits on one screen and keep colors.
+        command.env("LESS", "FRX");
+    }
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(_) => return io::stdout().write_all(output.as_bytes()),
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager closing early is not an error.
+        let _ = stdin.write_all(output.as_bytes());
+    }
+    child.wait()?;
     Ok(())
 }
 
 /// Runs the program in log mode
-pub fn run_log() -> io::Result<()> {
-    let repo = open_home_repo()?;
-    let head = match repo.head() {
-        Ok(head) => head,
-        Err(e) => {
-            eprintln!("Unable to get HEAD: {e}");
-            exit(74);
+pub fn run_log(args: LogArgs) -> Result<()> {
+    let home = open_git_home()?;
+    let options = LogOptions {
+        count: args.count,
+        since: args.since,
+        until: args.until,
+        paths: args.paths,
+    };
+
+    let mut output = String::new();
+    for commit in home.log(&options).context("Unable to walk history")? {
+        if args.oneline {
+            let id = commit.id.to_string();
+            output.push_str(&format!("{} {}\n", &id[..7], commit.summary()));
+        } else {
+            output.push_str(&gen_commit_header(&commit));
         }
+    }
+
+    Ok(print_paged(&output)?)
+}
+
+/// Runs the program in ls-files mode.
+pub fn run_ls_files(args: LsFilesArgs) -> Result<()> {
+    let home = open_git_home()?;
+    let tree = match &args.rev {
+        Some(rev) => Some(find_rev_tree(home.repo(), rev)?),
+        None => None,
+    };
+    let mut files = home.tracked(tree.as_ref())?;
+    if args.modified || args.deleted {
+        // The same changes status lists as not staged.
+        let changed: Vec<String> = home
+            .status()?
+            .unstaged
+            .into_iter()
+            .filter(|x| match x.kind {
+                ChangeKind::Deleted => args.deleted,
+                _ => args.modified,
+            })
+            .map(|x| x.path)
+            .collect();
+        files.retain(|x| changed.contains(&x.path));
+    }
+    Ok(print_tracked_files(&files, &args)?)
+}
+
+/// Runs the program in show mode.
+pub fn run_show(args: ShowArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let env = get_environment();
+    let spec = match &args.path {
+        Some(path) => format!("{}:{}", args.rev, path),
+        None => args.rev.clone(),
     };
-    let commit = match head.peel_to_commit() {
-        Ok(commit) => commit,
-        Err(e) => {
-            eprintln!("Unable to get commit from HEAD: {e}");
-            exit(74);
+    let (object, _reference) = repo
+        .revparse_ext(&spec)
+        .map_err(|e| Error::NotFound(format!("Unknown revision {}: {}", spec, e.message())))?;
+
+    if let Some(blob) = object.as_blob() {
+        let home_dir = repo.workdir().unwrap_or_else(|| Path::new(""));
+        let contents = match is_encrypted(blob.content()) {
+            true => match Key::load(home_dir, &env).and_then(|key| key.decrypt(blob.content())) {
+                Some(contents) => contents,
+                None => {
+                    return Err(Error::Refused(format!(
+                        "Could not decrypt {}, the key is missing or wrong",
+                        spec
+                    )));
+                }
+            },
+            false => blob.content().to_vec(),
+        };
+        return Ok(io::stdout().write_all(&contents)?);
+    }
+    let commit = object
+        .peel_to_commit()
+        .map_err(|_| Error::NotFound(format!("{} is neither a commit nor a file.", spec)))?;
+    print!("{}", gen_commit_header(&LogEntry::from(&commit)));
+
+    let parent_tree = commit.parent(0).ok().and_then(|parent| parent.tree().ok());
+    let mut options = DiffOptions::new();
+    options.old_prefix("$HOME/").new_prefix("$HOME/");
+    let diff = commit
+        .tree()
+        .and_then(|tree| {
+            repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut options))
+        })
+        .context(format!("Could not diff commit {}", commit.id()))?;
+    print_diff(&repo, &diff, false, args.color)
+}
+
+/// Returns true if `path` is one of `specs` or inside a directory in `specs`.
+fn matches_paths(path: &str, specs: &[String]) -> bool {
+    specs.iter().any(|spec| {
+        let spec = spec.trim_end_matches('/');
+        path == spec
+            || path
+                .strip_prefix(spec)
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+/// Looks up the commit `rev` points to.
+fn find_rev_commit<'a>(repo: &'a Repository, rev: &str) -> Result<git2::Object<'a>> {
+    repo.revparse_single(rev)
+        .and_then(|object| object.peel(ObjectType::Commit))
+        .map_err(|e| Error::NotFound(format!("Unknown revision {}: {}", rev, e.message())))
+}
+
+/// Runs the program in restore mode.
+pub fn run_restore(args: RestoreArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let env = get_environment();
+    let source = match &args.source {
+        Some(rev) => Some(find_rev_commit(&repo, rev)?),
+        None => None,
+    };
+
+    if args.staged {
+        let target = match source {
+            Some(object) => Some(object),
+            None => repo
+                .head()
+                .and_then(|head| head.peel(ObjectType::Commit))
+                .ok(),
+        };
+        repo.reset_default(target.as_ref(), args.paths.iter())
+            .context("Could not unstage files")?;
+        for i in args.paths.iter() {
+            println!("Unstaged $HOME/{}", i);
+        }
+        return Ok(());
+    }
+
+    let mut index = repo.index().context("Could not open index")?;
+    let mut entries = Vec::new();
+    match &source {
+        Some(object) => {
+            let tree = object.peel_to_tree().context("Could not read tree")?;
+            tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+                let path = format!("{}{}", root, entry.name().unwrap_or_default());
+                if entry.kind() == Some(ObjectType::Blob) && matches_paths(&path, &args.paths) {
+                    entries.push((path, entry.id()));
+                }
+                TreeWalkResult::Ok
+            })
+            .context("Could not read tree")?;
         }
+        None => {
+            for entry in index.iter() {
+                let path = String::from_utf8_lossy(&entry.path).into_owned();
+                if matches_paths(&path, &args.paths) {
+                    entries.push((path, entry.id));
+                }
+            }
+        }
+    }
+    if entries.is_empty() {
+        return Err(Error::NotFound(format!(
+            "No tracked files match {}",
+            args.paths.join(" ")
+        )));
+    }
+
+    let home_dir = repo
+        .workdir()
+        .unwrap_or_else(|| Path::new(""))
+        .to_path_buf();
+    let backup_dir = gen_backup_dir(&home_dir);
+    let mut backed_up = false;
+    let mut checkout = CheckoutBuilder::new();
+    // Files that differ are backed up first, so forcing only rewrites
+    // files with identical contents.
+    checkout.force().update_index(false);
+    for (path, id) in entries.iter() {
+        if differs_from_blob(&repo, &home_dir, Path::new(path), *id, &env) {
+            backup_file(&home_dir, Path::new(path), &backup_dir)?;
+            backed_up = true;
+        }
+        checkout.path(path);
+    }
+
+    match &source {
+        Some(object) => repo.checkout_tree(object, Some(&mut checkout)),
+        None => repo.checkout_index(Some(&mut index), Some(&mut checkout)),
+    }
+    .context("Could not restore files")?;
+    let source_tree = source
+        .as_ref()
+        .and_then(|object| object.peel_to_tree().ok());
+    let restored: Vec<String> = entries.iter().map(|(path, _)| path.clone()).collect();
+    write_home_files(&repo, source_tree.as_ref(), Some(&restored), &env)?;
+    for (path, _) in entries {
+        println!("Restored $HOME/{}", path);
+    }
+    if backed_up {
+        println!("Previous versions saved to {}", backup_dir.display());
+    }
+    Ok(())
+}
+
+/// Runs the program in reset mode.
+pub fn run_reset(args: ResetArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let env = get_environment();
+    let target = find_rev_commit(&repo, &args.rev);
+
+    if let ResetMode::Paths(paths) = args.mode {
+        let target = match target {
+            Ok(object) => Some(object),
+            // Unstaging before the first commit removes the files from the index.
+            Err(_) if args.rev == "HEAD" => None,
+            Err(e) => return Err(e),
+        };
+        repo.reset_default(target.as_ref(), paths.iter())
+            .context("Could not unstage files")?;
+        for i in paths {
+            println!("Unstaged $HOME/{}", i);
+        }
+        return Ok(());
+    }
+
+    let target = target?;
+    let kind = match args.mode {
+        ResetMode::Soft => ResetType::Soft,
+        ResetMode::Hard => {
+            backup_dirty_files(&repo, &env)?;
+            ResetType::Hard
+        }
+        _ => ResetType::Mixed,
     };
-    let sha = commit.id();
-    let author = commit.author();
-    let time = {
-        let from = commit.time().seconds();
-        let utc_val = Local.timestamp(from, 0).to_string();
-        utc_val
+    repo.reset(&target, kind, None)
+        .context(format!("Could not reset to {}", args.rev))?;
+    if kind == ResetType::Hard {
+        write_home_files(&repo, None, None, &env)?;
+    }
+    if let Some(commit) = target.as_commit() {
+        let id = commit.id().to_string();
+        println!(
+            "HEAD is now at {} {}",
+            &id[..7],
+            commit.summary().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// Moves every tracked file with changes in $HOME or in the index into a
+/// backup directory. A staged file is saved even when $HOME matches the index.
+fn backup_dirty_files(repo: &Repository, env: &Environment) -> Result<()> {
+    let home_dir = repo
+        .workdir()
+        .unwrap_or_else(|| Path::new(""))
+        .to_path_buf();
+    let backup_dir = gen_backup_dir(&home_dir);
+    let mut backed_up = false;
+    for i in get_dirty_files(repo, env)? {
+        let path = Path::new(&i);
+        if home_dir.join(path).symlink_metadata().is_ok() {
+            backup_file(&home_dir, path, &backup_dir)?;
+            backed_up = true;
+        }
+    }
+    if backed_up {
+        println!("Local changes saved to {}", backup_dir.display());
+    }
+    Ok(())
+}
+
+/// Runs the program in branch mode.
+pub fn run_branch(args: BranchArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let find_branch = |name: &str| {
+        repo.find_branch(name, BranchType::Local)
+            .map_err(|_| Error::NotFound(format!("Branch {} does not exist.", name)))
     };
-    let message = match commit.message() {
-        Some(message) => message,
-        None => "",
+
+    let result = match args.mode {
+        BranchMode::List => {
+            let current = current_branch(&repo);
+            let branches = repo
+                .branches(Some(BranchType::Local))
+                .context("Could not list branches")?;
+            for (branch, _) in branches.flatten() {
+                let name = branch.name().ok().flatten().unwrap_or_default();
+                let marker = if current.as_deref() == Some(name) {
+                    '*'
+                } else {
+                    ' '
+                };
+                println!("{marker} {name}");
+            }
+            Ok(())
+        }
+        BranchMode::Create(name, start) => {
+            let start = start.as_deref().unwrap_or("HEAD");
+            let commit = repo
+                .revparse_single(start)
+                .and_then(|object| object.peel_to_commit())
+                .map_err(|e| {
+                    Error::NotFound(format!("Unknown revision {}: {}", start, e.message()))
+                })?;
+            repo.branch(&name, &commit, false)
+                .map(|_| println!("Created branch {name}."))
+        }
+        BranchMode::Delete(name, force) => {
+            let mut branch = find_branch(&name)?;
+            let merged = match (
+                branch.get().target(),
+                repo.head().ok().and_then(|head| head.target()),
+            ) {
+                (Some(tip), Some(head)) => {
+                    tip == head || repo.graph_descendant_of(head, tip).unwrap_or(false)
+                }
+                _ => false,
+            };
+            if !merged && !force {
+                return Err(Error::Refused(format!(
+                    "Branch {} is not merged into HEAD.\nUse -D to delete it anyway.",
+                    name
+                )));
+            }
+            branch.delete().map(|_| println!("Deleted branch {name}."))
+        }
+        BranchMode::Rename(old, new) => {
+            let old = match old.or_else(|| current_branch(&repo)) {
+                Some(old) => old,
+                None => {
+                    return Err(Error::Usage(String::from(
+                        "HEAD is detached, name the branch to rename.",
+                    )));
+                }
+            };
+            find_branch(&old)?
+                .rename(&new, false)
+                .map(|_| println!("Renamed branch {old} to {new}."))
+        }
     };
-    println!("commit {sha}");
-    println!("Author: {author}");
-    println!("Date: {time}");
-    println!();
-    println!("   {message}");
-    println!();
+
+    result.context("branch error")
+}
+
+/// Runs the program in switch mode.
+///
+/// Nothing in $HOME is touched if any tracked file has changes.
+pub fn run_switch(args: SwitchArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let env = get_environment();
+    let dirty = get_dirty_files(&repo, &env)?;
+    if !dirty.is_empty() {
+        return Err(Error::Conflict {
+            message: String::from("Cannot switch branches with changes to tracked files:"),
+            paths: dirty,
+            hint: String::from("Commit or restore them first."),
+        });
+    }
+
+    if args.create {
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .context("Unable to get commit from HEAD")?;
+        repo.branch(&args.branch, &head, false)
+            .context(format!("Could not create branch {}", args.branch))?;
+    }
+    let branch = repo
+        .find_branch(&args.branch, BranchType::Local)
+        .map_err(|_| Error::NotFound(format!("Branch {} does not exist.", args.branch)))?;
+    let tree = branch
+        .get()
+        .peel_to_tree()
+        .context(format!("Could not read branch {}", args.branch))?;
+    let head_tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    let overwritten = find_overwritten_files(&repo, head_tree.as_ref(), &tree, &env)?;
+    if !overwritten.is_empty() {
+        return Err(Error::Conflict {
+            message: String::from("Switching would overwrite these untracked files:"),
+            paths: overwritten,
+            hint: String::from("Move them out of the way first."),
+        });
+    }
+
+    checkout_changed_files(&repo, head_tree.as_ref(), &tree, &env)?;
+    let refname = format!("refs/heads/{}", args.branch);
+    repo.set_head(&refname)
+        .context(format!("Could not switch to {}", args.branch))?;
+    println!("Switched to branch '{}'", args.branch);
     Ok(())
 }
 
-/// Runs the program in commit mode.
-pub fn run_commit(args: Vec<String>) -> io::Result<()> {
+/// Runs the program in profile mode.
+pub fn run_profile(list: bool) -> Result<()> {
     let repo = open_home_repo()?;
-    let args = CommitArgs::new(args)?;
-    if args.values[0] == "" {
-	eprintln!("Commit aborted");
-	exit(1);
+    let env = get_environment();
+    if list {
+        for profile in get_profiles(&repo) {
+            println!("{}", profile.name);
+            println!("\thosts: {}", profile.hosts.join(", "));
+            if let Some(branch) = profile.branch {
+                println!("\tbranch: {}", branch);
+            }
+            if let Some(overlay) = profile.overlay {
+                println!("\toverlay: $HOME/{}", overlay);
+            }
+        }
+        return Ok(());
     }
+
+    let hostname = get_hostname();
+    println!("Host: {}", hostname);
+    let (profile, resolution) = match resolve_profile(&repo, &hostname, &env) {
+        Some(found) => found,
+        None => {
+            match env.profile {
+                Some(name) => println!("No profile named '{}', set by $GIT_HOME_PROFILE.", name),
+                None => println!("No profile matches this host."),
+            }
+            return Ok(());
+        }
+    };
+    println!("Profile: {} ({})", profile.name, resolution);
+    if let Some(branch) = profile.branch {
+        println!("Branch: {} ({})", branch, gen_branch_string(&repo));
+    }
+    if let Some(overlay) = profile.overlay {
+        println!("Overlay: $HOME/{}", overlay);
+    }
+    Ok(())
+}
+
+/// Runs the program in template mode.
+pub fn run_template(args: TemplateArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let env = get_environment();
+    let home_dir = repo
+        .workdir()
+        .unwrap_or_else(|| Path::new(""))
+        .to_path_buf();
+    let mut index = repo.index().context("Could not open index")?;
+    let mut templates = get_template_paths(&home_dir);
+
     match args.mode {
-        CommitMode::Commit => {
-            let x = if let Ok(_) = repo.revparse_ext("HEAD") {
-                run_commit_action(&args.values)
-            } else {
-                run_initial_commit(&args.values)
+        TemplateMode::List => {
+            for i in templates {
+                println!("$HOME/{}", i);
+            }
+            return Ok(());
+        }
+        TemplateMode::Add(paths) => {
+            for i in paths.iter() {
+                if !home_dir.join(i).is_file() {
+                    return Err(Error::NotFound(format!("$HOME/{} is not a file", i)));
+                }
+                // Staged before it is marked, so the placeholders are
+                // stored exactly as written, and still encrypted if it is
+                // also a secret.
+                if !is_template(&home_dir, i) {
+                    stage_path(&repo, &mut index, Path::new(i), &env)?;
+                    templates.push(i.clone());
+                }
+            }
+        }
+        TemplateMode::Remove(paths) => {
+            for i in paths.iter() {
+                if !templates.contains(i) {
+                    return Err(Error::NotFound(format!("$HOME/{} is not a template", i)));
+                }
+            }
+            templates.retain(|x| !paths.contains(x));
+        }
+        TemplateMode::Render => {
+            let backup_dir = gen_backup_dir(&home_dir);
+            let mut backed_up = false;
+            for i in templates.iter() {
+                let id = match index.get_path(Path::new(i), 0) {
+                    Some(entry) => entry.id,
+                    None => continue,
+                };
+                if differs_from_blob(&repo, &home_dir, Path::new(i), id, &env) {
+                    backup_file(&home_dir, Path::new(i), &backup_dir)?;
+                    backed_up = true;
+                }
+            }
+            write_home_files(&repo, None, None, &env)?;
+            if backed_up {
+                println!("Previous versions saved to {}", backup_dir.display());
+            }
+            return Ok(());
+        }
+    }
+
+    set_template_paths(&home_dir, &templates)
+        .context(format!("Could not write $HOME/{}", TEMPLATES_FILE))?;
+    stage_path(&repo, &mut index, Path::new(TEMPLATES_FILE), &env)?;
+    index.write().context("could not write to index")?;
+    write_home_files(&repo, None, None, &env)
+}
+
+/// Runs the program in secrets mode.
+pub fn run_secrets(args: SecretsArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let env = get_environment();
+    let home_dir = repo
+        .workdir()
+        .unwrap_or_else(|| Path::new(""))
+        .to_path_buf();
+    let mut index = repo.index().context("Could not open index")?;
+    let mut patterns = get_secret_patterns(&home_dir);
+    let tracked: Vec<String> = index
+        .iter()
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+        .collect();
+
+    let restage = match args.mode {
+        SecretsMode::List => {
+            println!("Patterns:");
+            for i in patterns.iter() {
+                println!("\t{}", i);
+            }
+            println!("Protected files:");
+            for i in tracked.iter().filter(|x| matches_secret(&patterns, x)) {
+                let encrypted = index
+                    .get_path(Path::new(i), 0)
+                    .and_then(|entry| repo.find_blob(entry.id).ok())
+                    .is_some_and(|blob| is_encrypted(blob.content()));
+                match encrypted {
+                    true => println!("\t$HOME/{}", i),
+                    false => println!(
+                        "\t$HOME/{} (not encrypted yet, run 'git home add' on it)",
+                        i
+                    ),
+                }
+            }
+            return Ok(());
+        }
+        SecretsMode::Add(new) => {
+            if Key::load(&home_dir, &env).is_none() {
+                Key::generate(&home_dir)
+                    .context(format!("Could not create $HOME/{}", GIT_HOME_KEY_FILE))?;
+                println!("Created a new key in $HOME/{}.", GIT_HOME_KEY_FILE);
+                println!("Copy it to your other machines, the secrets can not be read without it.");
+            }
+            let restage: Vec<String> = tracked
+                .iter()
+                .filter(|x| matches_secret(&new, x) && !matches_secret(&patterns, x))
+                .cloned()
+                .collect();
+            for i in new {
+                if !patterns.contains(&i) {
+                    patterns.push(i);
+                }
+            }
+            let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+            for i in restage.iter() {
+                if head
+                    .as_ref()
+                    .is_some_and(|tree| tree.get_path(Path::new(i)).is_ok())
+                {
+                    eprintln!(
+                        "warning: $HOME/{} is already committed in plaintext, past commits still contain it",
+                        i
+                    );
+                }
+            }
+            restage
+        }
+        SecretsMode::Remove(old) => {
+            for i in old.iter() {
+                if !patterns.contains(i) {
+                    return Err(Error::NotFound(format!("{} is not a secrets pattern", i)));
+                }
+            }
+            let previous = patterns.clone();
+            patterns.retain(|x| !old.contains(x));
+            tracked
+                .iter()
+                .filter(|x| matches_secret(&previous, x) && !matches_secret(&patterns, x))
+                .cloned()
+                .collect()
+        }
+        SecretsMode::Rekey => {
+            let key = match Key::load(&home_dir, &env) {
+                Some(key) => key,
+                None => {
+                    return Err(Error::Refused(String::from(
+                        "No key to decrypt the secrets with.",
+                    )))
+                }
+            };
+            // Everything is decrypted before the new key replaces the old one.
+            let mut secrets = Vec::new();
+            for entry in index.iter() {
+                let blob = match repo.find_blob(entry.id) {
+                    Ok(blob) => blob,
+                    Err(_) => continue,
+                };
+                if !is_encrypted(blob.content()) {
+                    continue;
+                }
+                match key.decrypt(blob.content()) {
+                    Some(plaintext) => secrets.push((entry, plaintext)),
+                    None => {
+                        return Err(Error::Refused(format!(
+                            "Could not decrypt $HOME/{}, the key is wrong",
+                            String::from_utf8_lossy(&entry.path)
+                        )));
+                    }
+                }
+            }
+
+            let new_key = match &env.new_passphrase {
+                Some(passphrase) => Key::Passphrase(passphrase.clone(), env.keys.clone()),
+                None => {
+                    let key_file = home_dir.join(GIT_HOME_KEY_FILE);
+                    if key_file.exists() {
+                        let old_key_file = home_dir.join(format!("{}.old", GIT_HOME_KEY_FILE));
+                        fs::rename(&key_file, old_key_file)
+                            .context("Could not move the old key aside")?;
+                        println!(
+                            "The old key is in $HOME/{}.old, past commits need it.",
+                            GIT_HOME_KEY_FILE
+                        );
+                    }
+                    Key::generate(&home_dir)
+                        .context(format!("Could not create $HOME/{}", GIT_HOME_KEY_FILE))?
+                }
             };
-            x
+            for (entry, plaintext) in secrets.iter() {
+                index
+                    .add_frombuffer(entry, &new_key.encrypt(plaintext)?)
+                    .context("index error")?;
+            }
+            index.write().context("could not write to index")?;
+            println!(
+                "Re-encrypted {} files, commit them to finish.",
+                secrets.len()
+            );
+            return Ok(());
+        }
+    };
+
+    set_secret_patterns(&home_dir, &patterns)
+        .context(format!("Could not write $HOME/{}", SECRETS_FILE))?;
+    stage_path(&repo, &mut index, Path::new(SECRETS_FILE), &env)?;
+    for i in restage.iter() {
+        if home_dir.join(i).is_file() {
+            stage_path(&repo, &mut index, Path::new(i), &env)?;
         }
     }
+    index.write().context("could not write to index")
 }
 
-/// Initializes a new git home directory.
-pub fn run_init() -> io::Result<()> {
-    let canonical_path = match resolve_git_repo() {
-        Ok(string) | Err(string) => string,
+/// Runs the program in ignore mode.
+pub fn run_ignore(args: IgnoreArgs) -> Result<()> {
+    let repo = open_home_repo()?;
+    let env = get_environment();
+    let home_dir = repo
+        .workdir()
+        .unwrap_or_else(|| Path::new(""))
+        .to_path_buf();
+    let mut lines = get_ignore_lines(&home_dir);
+
+    match args.mode {
+        IgnoreMode::List => {
+            for i in lines
+                .iter()
+                .filter(|x| !x.trim().is_empty() && !x.starts_with('#'))
+            {
+                println!("{}", i);
+            }
+            return Ok(());
+        }
+        IgnoreMode::Explain(paths) => {
+            for i in paths {
+                match explain_ignore(&repo, &home_dir, &i) {
+                    IgnoreReason::Pattern(line, pattern) => {
+                        println!(
+                            "$HOME/{}: ignored by '{}' ($HOME/{}:{})",
+                            i, pattern, IGNORE_FILE, line
+                        )
+                    }
+                    IgnoreReason::Negated(line, pattern) => {
+                        println!(
+                            "$HOME/{}: not ignored, kept by '{}' ($HOME/{}:{})",
+                            i, pattern, IGNORE_FILE, line
+                        )
+                    }
+                    IgnoreReason::Git => println!(
+                        "$HOME/{}: ignored by a .gitignore file or git's exclude settings",
+                        i
+                    ),
+                    IgnoreReason::NotIgnored => println!("$HOME/{}: not ignored", i),
+                }
+            }
+            return Ok(());
+        }
+        IgnoreMode::Add(patterns) => {
+            for i in patterns {
+                if !lines.contains(&i) {
+                    lines.push(i);
+                }
+            }
+        }
+        IgnoreMode::Remove(patterns) => {
+            for i in patterns.iter() {
+                if !lines.contains(i) {
+                    return Err(Error::NotFound(format!(
+                        "{} is not in $HOME/{}",
+                        i, IGNORE_FILE
+                    )));
+                }
+            }
+            lines.retain(|x| !patterns.contains(x));
+        }
+    }
+
+    set_ignore_lines(&home_dir, &lines)
+        .context(format!("Could not write $HOME/{}", IGNORE_FILE))?;
+    let mut index = repo.index().context("Could not open index")?;
+    stage_path(&repo, &mut index, Path::new(IGNORE_FILE), &env)?;
+    index.write().context("could not write to index")
+}
+
+/// Runs the program in config mode.
+pub fn run_config(args: ConfigArgs) -> Result<()> {
+    let mut config = load_config()?;
+    match args.mode {
+        ConfigMode::List => {
+            for setting in SETTINGS {
+                match config.get(setting.key) {
+                    Some((value, source)) => println!("{} = {} ({})", setting.key, value, source),
+                    None => println!("{} is not set", setting.key),
+                }
+            }
+        }
+        ConfigMode::Get(key) => {
+            if find_setting(&key).is_none() {
+                return Err(Error::NotFound(format!(
+                    "There is no setting called {}.",
+                    key
+                )));
+            }
+            match config.get(&key) {
+                Some((value, _)) => println!("{}", value),
+                None => return Err(Error::NotFound(format!("{} is not set.", key))),
+            }
+        }
+        ConfigMode::Set(key, value) => {
+            config.set(&key, &value)?;
+            config.save()?;
+            if let Some((_, source @ Source::Env(_))) = config.get(&key) {
+                eprintln!(
+                    "warning: {} is {}, which takes precedence over the config file.",
+                    key, source
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs the program in doctor mode, failing if any check fails.
+pub fn run_doctor() -> Result<()> {
+    let home_dir = env::var_os("HOME").map(PathBuf::from);
+    let checks = diagnose(home_dir.as_deref());
+    print_checks(&checks, has_color());
+    match checks.iter().filter(|x| x.outcome == Outcome::Fail).count() {
+        0 => Ok(()),
+        failed => Err(Error::ChecksFailed(failed)),
+    }
+}
+
+/// Runs the program in completions mode.
+///
+/// Listing candidates never prompts to create the home repo and prints
+/// nothing if it does not exist, as it runs while the user is typing.
+pub fn run_completions(args: CompletionsArgs) -> Result<()> {
+    let kind = match args.mode {
+        CompletionsMode::Script(shell) => {
+            print!("{}", gen_completions(&shell));
+            return Ok(());
+        }
+        kind => kind,
+    };
+    let home_dir = get_home_dir()?;
+    // Warnings would end up in the middle of the user's command line.
+    let repo = match GitHome::open(resolve_git_repo()?, &home_dir, Environment::default()) {
+        Ok(home) => home.into_repo(),
+        Err(_) => return Ok(()),
     };
-    let git_home_path = Path::new(&canonical_path);
 
-    match Repository::init_bare(&git_home_path) {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            eprintln!("Could no create git home repo: {}", e);
-            exit(74);
+    let candidates: Vec<String> = match kind {
+        CompletionsMode::Tracked => {
+            let index = repo.index().context("Could not open index")?;
+            // Paths are completed relative to where the user is.
+            let current_dir = env::current_dir()?;
+            index
+                .iter()
+                .map(|entry| {
+                    Path::new(&home_dir).join(String::from_utf8_lossy(&entry.path).as_ref())
+                })
+                .map(|path| match path.strip_prefix(&current_dir) {
+                    Ok(relative) => relative.to_string_lossy().into_owned(),
+                    Err(_) => path.to_string_lossy().into_owned(),
+                })
+                .collect()
         }
+        CompletionsMode::Branches => repo
+            .branches(Some(BranchType::Local))
+            .context("Could not list branches")?
+            .flatten()
+            .filter_map(|(branch, _)| branch.name().ok().flatten().map(String::from))
+            .collect(),
+        _ => repo
+            .remotes()
+            .context("Could not list remotes")?
+            .iter()
+            .flatten()
+            .map(String::from)
+            .collect(),
+    };
+    for i in candidates {
+        println!("{}", i);
     }
+    Ok(())
+}
+
+/// Runs the program in commit mode.
+pub fn run_commit(args: CommitArgs) -> Result<()> {
+    let home = open_git_home()?;
+    home.commit(&args.message)?;
+    Ok(())
 }
 
-pub fn run_passthrough(prefix_args: Option<Box<ProgMode>>, args: Vec<String>) -> io::Result<()> {
+/// Initializes a new git home directory.
+pub fn run_init() -> Result<()> {
+    let canonical_path = resolve_git_repo()?;
+    let home_dir = get_home_dir()?;
+
+    init_git_home(&canonical_path, &home_dir).context("Could no create git home repo")?;
+    Ok(())
+}
+
+/// Runs any git home command before the double dash, then git with the rest
+/// of the arguments. Returns the code git exited with.
+pub fn run_passthrough(prefix_args: Option<Box<ProgMode>>, args: Vec<String>) -> Result<i32> {
     if let Some(prefix_args) = prefix_args {
-	match *prefix_args {
-	    ProgMode::Add(args) => run_add(args)?,
+        match *prefix_args {
+            ProgMode::Add(args) => run_add(args)?,
+            ProgMode::Rm(args) => run_rm(args)?,
+            ProgMode::Diff(args) => run_diff(args)?,
+            ProgMode::Remote(args) => run_remote(args)?,
+            ProgMode::Push(args) => run_push(args)?,
+            ProgMode::Fetch(args) => run_fetch(args)?,
+            ProgMode::Pull(args) => run_pull(args)?,
+            ProgMode::Clone(url) => run_clone(url)?,
+            ProgMode::Show(args) => run_show(args)?,
+            ProgMode::Restore(args) => run_restore(args)?,
+            ProgMode::Reset(args) => run_reset(args)?,
+            ProgMode::Branch(args) => run_branch(args)?,
+            ProgMode::Switch(args) => run_switch(args)?,
+            ProgMode::Profile(list) => run_profile(list)?,
+            ProgMode::Template(args) => run_template(args)?,
+            ProgMode::Secrets(args) => run_secrets(args)?,
+            ProgMode::Ignore(args) => run_ignore(args)?,
+            ProgMode::Config(args) => run_config(args)?,
+            ProgMode::Doctor => run_doctor()?,
+            ProgMode::Completions(args) => run_completions(args)?,
+            ProgMode::Manpage => run_manpage()?,
+            ProgMode::LsFiles(args) => run_ls_files(args)?,
             ProgMode::Init => run_init()?,
-            ProgMode::Status(color) => print_repo_status(color)?,
-            ProgMode::Commit(args) => run_commit(args.values)?,
-            ProgMode::Log => run_log()?,
-            ProgMode::Help | ProgMode::Passthrough(_,_) => print_usage()?,
-	    ProgMode::None => (),
-	}
+            ProgMode::Status(args) => print_repo_status(args)?,
+            ProgMode::Commit(args) => run_commit(args)?,
+            ProgMode::Log(args) => run_log(args)?,
+            ProgMode::Help(command) => print_help(command)?,
+            ProgMode::Version => print_version()?,
+            ProgMode::Passthrough(_, _) => print_usage()?,
+            ProgMode::None => (),
+        }
     };
-    
-    let home_dir = env::var("HOME").unwrap_or_else(|err| {
-	eprintln!("unable to get value of $HOME: {}", err);
-	exit(74);
-    });
-    let git_dir = env::var("GIT_HOME_DIR").unwrap_or(GIT_HOME_DIR.to_string());
+
+    let home_dir = get_home_dir()?;
+    let git_dir = resolve_git_repo()?;
 
     let mut git = Command::new("git")
-        .args(&[
+        .args([
             "-C",
-	    &home_dir,
+            &home_dir,
             "--work-tree",
             ".",
             "--git-dir",
-	    &git_dir,
+            &git_dir,
             "-c",
             "status.showUntrackedFiles=no",
-	    "--no-pager"
+            "--no-pager",
         ])
         .args(args)
         .stdout(Stdio::inherit())
         .spawn()
-        .expect("Could not spawn process.");
-    match git.wait() {
-	Ok(status) => match status.code() {
-	    Some(code) => exit(code),
-	    None => {
-		println!("Git terminated by signal");
-		exit(0)
-	    }
-	}
-	Err(err) => {
-	    eprintln!("{}", err);
-	    exit(74)
-	}
+        .context("Could not spawn git")?;
+    let status = git.wait().context("git failed")?;
+    match status.code() {
+        Some(code) => Ok(code),
+        None => {
+            println!("Git terminated by signal");
+            Ok(0)
+        }
     }
+}
 
+/// Runs the program in manpage mode.
+pub fn run_manpage() -> Result<()> {
+    print!("{}", gen_manpage());
+    Ok(())
 }
@@ -0,0 +1,394 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Encrypted secrets.
+//!
+//! Files matching a pattern in `$HOME/.githomesecrets` are encrypted before
+//! they are written to the repo and decrypted when checked out into $HOME.
+//! The key is either the random key in `$HOME/.config/git_home_key`, which
+//! never leaves the machine unless copied by hand, or the passphrase in
+//! [`Environment::passphrase`], which `git home` takes from the
+//! GIT_HOME_PASSPHRASE environment variable.
+//!
+//! Encrypted blobs start with a short header naming the kind of key used,
+//! followed by the salt and nonce and the XChaCha20-Poly1305 ciphertext.
+
+use super::{Environment, GIT_HOME_KEY_FILE};
+use crate::error::{Error, Result};
+use crate::profile::wildcard_match;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use sha2::Sha256;
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// The tracked file listing the patterns of files to encrypt, relative to $HOME.
+pub const SECRETS_FILE: &str = ".githomesecrets";
+
+const MAGIC: &[u8] = b"GITHOMESECRET1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+const PASSPHRASE_ROUNDS: u32 = 200_000;
+
+/// A passphrase, a salt and the key derived from them.
+type DerivedKey = (String, [u8; SALT_LEN], [u8; 32]);
+
+#[derive(Default)]
+struct Cache {
+    /// The salt files encrypted with a passphrase use.
+    salt: Option<[u8; SALT_LEN]>,
+    derived: Vec<DerivedKey>,
+}
+
+/// The keys derived from passphrases so far and the salt new files are
+/// encrypted with, so a passphrase is only stretched once however many
+/// files are added. Clones share the same cache.
+#[derive(Clone, Default)]
+pub struct KeyCache(Arc<Mutex<Cache>>);
+
+impl fmt::Debug for KeyCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyCache").finish_non_exhaustive()
+    }
+}
+
+/// What the files are encrypted with.
+pub enum Key {
+    /// The contents of the key file.
+    File([u8; 32]),
+    /// A passphrase, with the cache of keys derived from it.
+    Passphrase(String, KeyCache),
+}
+
+impl Key {
+    /// Loads the key for this machine. The passphrase in `env` wins over
+    /// the key file, and a key file that is not a valid key is reported to
+    /// `env.warn`.
+    pub fn load(home_dir: &Path, env: &Environment) -> Option<Key> {
+        if let Some(passphrase) = &env.passphrase {
+            return Some(Key::Passphrase(passphrase.clone(), env.keys.clone()));
+        }
+        let contents = fs::read_to_string(home_dir.join(GIT_HOME_KEY_FILE)).ok()?;
+        match decode_hex(contents.trim()) {
+            Some(key) => Some(Key::File(key)),
+            None => {
+                (env.warn)(&format!("$HOME/{} is not a valid key", GIT_HOME_KEY_FILE));
+                None
+            }
+        }
+    }
+
+    /// Creates a new random key and writes it to the key file, readable by
+    /// the user only.
+    pub fn generate(home_dir: &Path) -> io::Result<Key> {
+        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let path = home_dir.join(GIT_HOME_KEY_FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        writeln!(file, "{}", encode_hex(&key))?;
+        Ok(Key::File(key.into()))
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            Key::File(_) => b'K',
+            Key::Passphrase(..) => b'P',
+        }
+    }
+
+    fn cipher(&self, salt: &[u8; SALT_LEN]) -> XChaCha20Poly1305 {
+        let key = match self {
+            Key::File(key) => *key,
+            Key::Passphrase(passphrase, cache) => derive_key(passphrase, salt, cache),
+        };
+        XChaCha20Poly1305::new(&key.into())
+    }
+
+    /// Encrypts `plaintext` into the format stored in the repo.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let salt = match self {
+            Key::File(_) => {
+                let mut salt = [0; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                salt
+            }
+            // Reusing the salt for the whole run means the passphrase is
+            // only stretched once however many files are added.
+            Key::Passphrase(_, cache) => {
+                let mut cache = cache.0.lock().unwrap_or_else(|e| e.into_inner());
+                *cache.salt.get_or_insert_with(|| {
+                    let mut salt = [0; SALT_LEN];
+                    OsRng.fill_bytes(&mut salt);
+                    salt
+                })
+            }
+        };
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher(&salt)
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| Error::Io(io::Error::other("Could not encrypt file.")))?;
+
+        let mut output = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        output.extend_from_slice(MAGIC);
+        output.push(self.kind());
+        output.extend_from_slice(&salt);
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&ciphertext);
+        Ok(output)
+    }
+
+    /// Decrypts a blob made by `encrypt`. Returns None if it was encrypted
+    /// with a different key.
+    pub fn decrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if !is_encrypted(data) || data.len() < HEADER_LEN || data[MAGIC.len()] != self.kind() {
+            return None;
+        }
+        let salt_start = MAGIC.len() + 1;
+        let nonce_start = salt_start + SALT_LEN;
+        let salt: [u8; SALT_LEN] = data[salt_start..nonce_start].try_into().ok()?;
+        let nonce = XNonce::from_slice(&data[nonce_start..HEADER_LEN]);
+        self.cipher(&salt).decrypt(nonce, &data[HEADER_LEN..]).ok()
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8; SALT_LEN], cache: &KeyCache) -> [u8; 32] {
+    let mut cache = cache.0.lock().unwrap_or_else(|e| e.into_inner());
+    let keys = &mut cache.derived;
+    if let Some((_, _, key)) = keys.iter().find(|(p, s, _)| p == passphrase && s == salt) {
+        return *key;
+    }
+    let key =
+        pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(passphrase.as_bytes(), salt, PASSPHRASE_ROUNDS);
+    keys.push((String::from(passphrase), *salt, key));
+    key
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<[u8; 32]> {
+    if text.len() != 64 || !text.is_ascii() {
+        return None;
+    }
+    let mut key = [0; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+/// Returns true if `data` is an encrypted blob.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Returns the patterns of the files to encrypt.
+pub fn get_secret_patterns(home_dir: &Path) -> Vec<String> {
+    match fs::read_to_string(home_dir.join(SECRETS_FILE)) {
+        Ok(contents) => contents
+            .lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty() && !x.starts_with('#'))
+            .map(String::from)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Overwrites the list of patterns of files to encrypt.
+pub fn set_secret_patterns(home_dir: &Path, patterns: &[String]) -> io::Result<()> {
+    let mut contents = String::from("# Files git home encrypts before they are committed.\n");
+    for i in patterns {
+        contents.push_str(i);
+        contents.push('\n');
+    }
+    fs::write(home_dir.join(SECRETS_FILE), contents)
+}
+
+/// Returns true if `path` matches one of `patterns`. A pattern naming a
+/// directory covers everything in it.
+pub fn matches_secret(patterns: &[String], path: &str) -> bool {
+    patterns.iter().any(|pattern| {
+        let dir = pattern.trim_end_matches('/');
+        wildcard_match(pattern, path)
+            || path
+                .strip_prefix(dir)
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+/// Returns true if `path` should be encrypted.
+pub fn is_secret(home_dir: &Path, path: &str) -> bool {
+    matches_secret(&get_secret_patterns(home_dir), path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn file_key(byte: u8) -> Key {
+        Key::File([byte; 32])
+    }
+
+    fn passphrase_key(passphrase: &str) -> Key {
+        Key::Passphrase(String::from(passphrase), KeyCache::default())
+    }
+
+    fn salt(encrypted: &[u8]) -> &[u8] {
+        &encrypted[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN]
+    }
+
+    #[test]
+    fn file_key_round_trip() {
+        let key = file_key(1);
+        let encrypted = key.encrypt(b"password=hunter2\n").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.windows(7).any(|x| x == b"hunter2"));
+        assert_eq!(key.decrypt(&encrypted).unwrap(), b"password=hunter2\n");
+    }
+
+    #[test]
+    fn passphrase_round_trip() {
+        let key = passphrase_key("correct horse");
+        let encrypted = key.encrypt(b"secret").unwrap();
+        assert_eq!(key.decrypt(&encrypted).unwrap(), b"secret");
+        assert!(passphrase_key("wrong horse").decrypt(&encrypted).is_none());
+    }
+
+    #[test]
+    fn passphrase_salt_is_kept_in_the_cache() {
+        let cache = KeyCache::default();
+        let key = Key::Passphrase(String::from("correct horse"), cache.clone());
+        let first = key.encrypt(b"one").unwrap();
+        let second = Key::Passphrase(String::from("correct horse"), cache)
+            .encrypt(b"two")
+            .unwrap();
+        assert_eq!(salt(&first), salt(&second));
+
+        let other = passphrase_key("correct horse").encrypt(b"one").unwrap();
+        assert_ne!(salt(&first), salt(&other));
+        assert_eq!(
+            passphrase_key("correct horse").decrypt(&first).unwrap(),
+            b"one"
+        );
+    }
+
+    #[test]
+    fn wrong_key_does_not_decrypt() {
+        let encrypted = file_key(1).encrypt(b"secret").unwrap();
+        assert!(file_key(2).decrypt(&encrypted).is_none());
+        // A blob made with a key file is never tried with a passphrase.
+        assert!(passphrase_key("secret").decrypt(&encrypted).is_none());
+    }
+
+    #[test]
+    fn damaged_blobs_do_not_decrypt() {
+        let key = file_key(1);
+        let mut encrypted = key.encrypt(b"secret").unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+        assert!(key.decrypt(&encrypted).is_none());
+        assert!(key.decrypt(&encrypted[..HEADER_LEN - 1]).is_none());
+        assert!(key.decrypt(b"secret").is_none());
+    }
+
+    #[test]
+    fn encryption_is_not_deterministic() {
+        let key = file_key(1);
+        assert_ne!(
+            key.encrypt(b"secret").unwrap(),
+            key.encrypt(b"secret").unwrap()
+        );
+    }
+
+    #[test]
+    fn load_reads_the_generated_key() {
+        let home = tempfile::tempdir().unwrap();
+        let encrypted = Key::generate(home.path())
+            .unwrap()
+            .encrypt(b"secret")
+            .unwrap();
+        let key = Key::load(home.path(), &Environment::default()).unwrap();
+        assert_eq!(key.decrypt(&encrypted).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn load_prefers_the_passphrase() {
+        let home = tempfile::tempdir().unwrap();
+        Key::generate(home.path()).unwrap();
+        let env = Environment {
+            passphrase: Some(String::from("correct horse")),
+            ..Environment::default()
+        };
+        assert!(matches!(
+            Key::load(home.path(), &env),
+            Some(Key::Passphrase(..))
+        ));
+    }
+
+    #[test]
+    fn load_warns_about_invalid_keys() {
+        static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+        let home = tempfile::tempdir().unwrap();
+        let env = Environment {
+            warn: |_| {
+                WARNINGS.fetch_add(1, Ordering::SeqCst);
+            },
+            ..Environment::default()
+        };
+        assert!(Key::load(home.path(), &env).is_none());
+        assert_eq!(WARNINGS.load(Ordering::SeqCst), 0);
+
+        fs::create_dir_all(home.path().join(".config")).unwrap();
+        fs::write(home.path().join(GIT_HOME_KEY_FILE), "not a key\n").unwrap();
+        assert!(Key::load(home.path(), &env).is_none());
+        assert_eq!(WARNINGS.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let key = [0xab; 32];
+        assert_eq!(decode_hex(&encode_hex(&key)), Some(key));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex(&"zz".repeat(32)), None);
+    }
+
+    #[test]
+    fn secret_patterns() {
+        let patterns = vec![String::from(".ssh/"), String::from("*.pem")];
+        assert!(matches_secret(&patterns, ".ssh/config"));
+        assert!(matches_secret(&patterns, "certs/server.pem"));
+        assert!(!matches_secret(&patterns, ".sshrc"));
+    }
+}
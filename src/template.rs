@@ -0,0 +1,356 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Templates for files that only differ between machines by a few values.
+//!
+//! Files listed in `$HOME/.githometemplates` are stored in the repo with
+//! `{{ name }}` placeholders and written into $HOME with the placeholders
+//! replaced by the values for the current machine. The values are
+//! `hostname`, `user`, `os` and any `name = value` lines in
+//! `$HOME/.config/git_home_values`.
+
+use super::{Environment, GIT_HOME_VALUES_FILE};
+use crate::profile::get_hostname;
+use std::{fs, io, path::Path};
+
+/// The tracked file listing which files are templates, relative to $HOME.
+pub const TEMPLATES_FILE: &str = ".githometemplates";
+
+/// Returns the paths, relative to $HOME, of the files marked as templates.
+pub fn get_template_paths(home_dir: &Path) -> Vec<String> {
+    match fs::read_to_string(home_dir.join(TEMPLATES_FILE)) {
+        Ok(contents) => contents
+            .lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty() && !x.starts_with('#'))
+            .map(String::from)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Overwrites the list of files marked as templates.
+pub fn set_template_paths(home_dir: &Path, paths: &[String]) -> io::Result<()> {
+    let mut contents = String::from("# Files git home renders with machine specific values.\n");
+    for i in paths {
+        contents.push_str(i);
+        contents.push('\n');
+    }
+    fs::write(home_dir.join(TEMPLATES_FILE), contents)
+}
+
+/// Returns the values placeholders are replaced with on this machine.
+///
+/// Later entries take precedence, so the values file can override the
+/// built in ones. Malformed lines in it are reported to `env.warn`.
+pub fn get_template_values(home_dir: &Path, env: &Environment) -> Vec<(String, String)> {
+    let mut values = vec![
+        (String::from("hostname"), get_hostname()),
+        (String::from("user"), env.user.clone()),
+        (String::from("os"), String::from(std::env::consts::OS)),
+    ];
+    if let Ok(contents) = fs::read_to_string(home_dir.join(GIT_HOME_VALUES_FILE)) {
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, value)) => {
+                    values.push((String::from(key.trim()), String::from(value.trim())))
+                }
+                None => (env.warn)(&format!(
+                    "ignoring malformed line in $HOME/{}: {}",
+                    GIT_HOME_VALUES_FILE, line
+                )),
+            }
+        }
+    }
+    values
+}
+
+fn lookup<'a>(values: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    values
+        .iter()
+        .rev()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.as_str())
+}
+
+/// A placeholder in a template, and where its value went in the rendered text.
+struct Span<'a> {
+    start: usize,
+    end: usize,
+    /// The placeholder as written, such as `{{ user }}`.
+    placeholder: &'a str,
+}
+
+/// Renders `template`, returning the spans of the output each known
+/// placeholder was replaced with.
+fn render_spans<'a>(template: &'a str, values: &[(String, String)]) -> (String, Vec<Span<'a>>) {
+    let mut output = String::new();
+    let mut spans = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end + 2,
+            None => break,
+        };
+        output.push_str(&rest[..start]);
+        match lookup(values, rest[start + 2..end - 2].trim()) {
+            Some(value) => {
+                let start_in_output = output.len();
+                output.push_str(value);
+                spans.push(Span {
+                    start: start_in_output,
+                    end: output.len(),
+                    placeholder: &rest[start..end],
+                });
+            }
+            None => output.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    (output, spans)
+}
+
+/// Replaces every `{{ name }}` with its value. Unknown names are left as is.
+pub fn render_template(template: &str, values: &[(String, String)]) -> String {
+    render_spans(template, values).0
+}
+
+/// The length in bytes of the common prefix of `a` and `b`.
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i)
+}
+
+/// The length in bytes of the common suffix of `a` and `b`.
+fn common_suffix(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .rev()
+        .zip(b.chars().rev())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, x), _)| {
+            a.len() - i - x.len_utf8()
+        })
+}
+
+/// Puts the placeholders of `old`, a line of the previous template, back
+/// into `line`, the line the user changed it to.
+///
+/// Only placeholders whose value is in the unchanged start or end of the
+/// line are put back. Values anywhere else are kept as text, as there is no
+/// telling whether the user meant the placeholder or the literal value.
+fn unrender_line(old: &str, line: &str, values: &[(String, String)]) -> String {
+    let (old_rendered, spans) = render_spans(old, values);
+    let prefix = common_prefix(&old_rendered, line);
+    let suffix = common_suffix(&old_rendered[prefix..], &line[prefix..]);
+    let old_suffix_start = old_rendered.len() - suffix;
+
+    let mut output = String::new();
+    let mut pos = 0;
+    for span in spans {
+        let (start, end) = if span.end <= prefix {
+            (span.start, span.end)
+        } else if span.start >= old_suffix_start {
+            (
+                span.start - old_suffix_start + line.len() - suffix,
+                span.end - old_suffix_start + line.len() - suffix,
+            )
+        } else {
+            continue;
+        };
+        output.push_str(&line[pos..start]);
+        output.push_str(span.placeholder);
+        pos = end;
+    }
+    output.push_str(&line[pos..]);
+    output
+}
+
+/// Turns the rendered contents of a file back into a template.
+///
+/// Lines that still match the rendered form of a line in `template` keep the
+/// template's version, so placeholders are never lost. A changed line keeps
+/// the placeholders of the line it replaced where the text around them is
+/// unchanged, and new lines are stored as they are. Without a previous
+/// template the contents are stored as they are.
+pub fn unrender_template(
+    template: Option<&str>,
+    rendered: &str,
+    values: &[(String, String)],
+) -> String {
+    let template = match template {
+        Some(template) => template,
+        None => return String::from(rendered),
+    };
+    let old: Vec<&str> = template.split_inclusive('\n').collect();
+    let old_rendered: Vec<String> = old.iter().map(|x| render_template(x, values)).collect();
+    let new: Vec<&str> = rendered.split_inclusive('\n').collect();
+
+    // Longest common subsequence of the rendered template and the new lines.
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old_rendered[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut output = String::new();
+    // The old lines removed since the last matching line, which the lines
+    // added in their place are paired with in order.
+    let mut removed: Vec<&str> = Vec::new();
+    let mut paired = 0;
+    let (mut i, mut j) = (0, 0);
+    while j < new.len() {
+        if i < old.len() && old_rendered[i] == new[j] {
+            output.push_str(old[i]);
+            removed.clear();
+            paired = 0;
+            i += 1;
+            j += 1;
+        } else if i < old.len() && lengths[i + 1][j] >= lengths[i][j + 1] {
+            removed.push(old[i]);
+            i += 1;
+        } else {
+            match removed.get(paired) {
+                Some(old_line) => output.push_str(&unrender_line(old_line, new[j], values)),
+                None => output.push_str(new[j]),
+            }
+            paired += 1;
+            j += 1;
+        }
+    }
+    output
+}
+
+/// Returns true if `path` is marked as a template.
+pub fn is_template(home_dir: &Path, path: &str) -> bool {
+    get_template_paths(home_dir).iter().any(|x| x == path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Vec<(String, String)> {
+        vec![
+            (String::from("user"), String::from("me")),
+            (String::from("os"), String::from("linux")),
+            (String::from("hostname"), String::from("box")),
+        ]
+    }
+
+    #[test]
+    fn render_replaces_known_placeholders() {
+        let rendered = render_template("{{ user }}@{{hostname}} {{ other }}", &values());
+        assert_eq!(rendered, "me@box {{ other }}");
+    }
+
+    #[test]
+    fn unchanged_file_round_trips() {
+        let template = "user = {{ user }}\nhome = /home/{{ user }}\nplain line\n";
+        let rendered = render_template(template, &values());
+        assert_eq!(
+            unrender_template(Some(template), &rendered, &values()),
+            template
+        );
+    }
+
+    #[test]
+    fn without_previous_template_contents_are_kept() {
+        let rendered = "/home/me/linux-src\n";
+        assert_eq!(unrender_template(None, rendered, &values()), rendered);
+    }
+
+    #[test]
+    fn new_lines_are_stored_verbatim() {
+        let template = "first\n";
+        let rendered = "first\n/home/me/linux-src\n";
+        assert_eq!(
+            unrender_template(Some(template), rendered, &values()),
+            rendered
+        );
+    }
+
+    #[test]
+    fn changed_line_without_placeholders_is_stored_verbatim() {
+        let template = "path = /tmp\n";
+        let rendered = "path = /home/me/linux-src\n";
+        assert_eq!(
+            unrender_template(Some(template), rendered, &values()),
+            rendered
+        );
+    }
+
+    #[test]
+    fn changed_line_keeps_placeholders_around_the_change() {
+        let template = "dir = /home/{{ user }}/src on {{ os }}\n";
+        let rendered = "dir = /home/me/src2 on linux\n";
+        let expected = "dir = /home/{{ user }}/src2 on {{ os }}\n";
+        assert_eq!(
+            unrender_template(Some(template), rendered, &values()),
+            expected
+        );
+    }
+
+    #[test]
+    fn placeholder_inside_the_change_is_dropped() {
+        let template = "{{ os }}-{{ user }}\n";
+        let rendered = "linux-you\n";
+        assert_eq!(
+            unrender_template(Some(template), rendered, &values()),
+            "{{ os }}-you\n"
+        );
+    }
+
+    #[test]
+    fn lines_are_matched_around_insertions_and_deletions() {
+        let template = "one {{ user }}\ntwo\nthree {{ os }}\n";
+        let rendered = "zero\none me\nthree linux\nfour\n";
+        let expected = "zero\none {{ user }}\nthree {{ os }}\nfour\n";
+        assert_eq!(
+            unrender_template(Some(template), rendered, &values()),
+            expected
+        );
+    }
+
+    #[test]
+    fn replaced_lines_are_paired_in_order() {
+        let template = "a={{ user }}\nb={{ os }}\nend\n";
+        let rendered = "a=me!\nb=linux!\nextra me\nend\n";
+        let expected = "a={{ user }}!\nb={{ os }}!\nextra me\nend\n";
+        assert_eq!(
+            unrender_template(Some(template), rendered, &values()),
+            expected
+        );
+    }
+
+    #[test]
+    fn common_prefix_and_suffix_respect_char_boundaries() {
+        assert_eq!(common_prefix("héllo", "héllp"), 5);
+        assert_eq!(common_suffix("aé", "bé"), 2);
+        assert_eq!(common_prefix("abc", "ab"), 2);
+        assert_eq!(common_suffix("abc", "bc"), 2);
+    }
+}
@@ -0,0 +1,76 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+use git_home::git::current_branch;
+
+/// A home repo whose `laptop` branch changes `.bashrc` and adds `.laptoprc`,
+/// with `master` checked out.
+fn home_with_branches() -> TestHome {
+    let home = TestHome::init();
+    home.write(".bashrc", "master\n");
+    home.run(&["add", ".bashrc"]);
+    home.run(&["commit", "-m", "Add .bashrc"]);
+    home.run(&["switch", "-c", "laptop"]);
+    home.write(".bashrc", "laptop\n");
+    home.write(".laptoprc", "laptop only\n");
+    home.run(&["add", ".bashrc", ".laptoprc"]);
+    home.run(&["commit", "-m", "Laptop settings"]);
+    home.run(&["switch", "master"]);
+    home
+}
+
+#[test]
+fn switch_checks_out_the_branch_files() {
+    let home = home_with_branches();
+    assert_eq!(current_branch(&home.repo()).as_deref(), Some("master"));
+    assert_eq!(home.read(".bashrc"), "master\n");
+    assert!(!home.join(".laptoprc").exists());
+
+    assert_eq!(
+        home.run(&["switch", "laptop"]),
+        "Switched to branch 'laptop'\n"
+    );
+    assert_eq!(current_branch(&home.repo()).as_deref(), Some("laptop"));
+    assert_eq!(home.read(".bashrc"), "laptop\n");
+    assert_eq!(home.read(".laptoprc"), "laptop only\n");
+}
+
+#[test]
+fn switch_refuses_with_dirty_files() {
+    let home = home_with_branches();
+    home.write(".bashrc", "changed\n");
+
+    let output = home.try_run(&["switch", "laptop"]);
+    assert_eq!(output.status.code(), Some(75));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("$HOME/.bashrc"));
+    assert_eq!(current_branch(&home.repo()).as_deref(), Some("master"));
+    assert_eq!(home.read(".bashrc"), "changed\n");
+}
+
+#[test]
+fn switch_refuses_to_overwrite_untracked_files() {
+    let home = home_with_branches();
+    home.write(".laptoprc", "mine\n");
+
+    let output = home.try_run(&["switch", "laptop"]);
+    assert_eq!(output.status.code(), Some(75));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("$HOME/.laptoprc"));
+    assert_eq!(current_branch(&home.repo()).as_deref(), Some("master"));
+    assert_eq!(home.read(".laptoprc"), "mine\n");
+}
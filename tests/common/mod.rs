@@ -0,0 +1,144 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Runs git home against a throwaway home directory.
+
+#![allow(dead_code)]
+
+use git2::Repository;
+use git_home::GIT_HOME_BACKUP_DIR;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+use tempfile::TempDir;
+
+/// A home directory with git home's repo in the default place.
+pub struct TestHome {
+    dir: TempDir,
+}
+
+impl TestHome {
+    /// Creates an empty home directory with a git identity.
+    pub fn new() -> TestHome {
+        // git home expects $HOME to be /<dir>/$USER, as /home/$USER is.
+        let dir = tempfile::Builder::new()
+            .prefix("git-home-test-")
+            .tempdir_in("/tmp")
+            .expect("could not create a home directory");
+        let home = TestHome { dir };
+        home.write(
+            ".gitconfig",
+            "[user]\n\tname = Test\n\temail = test@example.com\n",
+        );
+        home
+    }
+
+    /// Creates a home directory and runs `git home init` in it.
+    pub fn init() -> TestHome {
+        let home = TestHome::new();
+        home.run(&["init"]);
+        home
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// The absolute path of `path`, relative to $HOME.
+    pub fn join(&self, path: &str) -> PathBuf {
+        self.path().join(path)
+    }
+
+    /// `git home args`, run in $HOME with nothing else from the environment.
+    pub fn command(&self, args: &[&str]) -> Command {
+        let user = self.path().file_name().unwrap_or_default();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_git-home"));
+        command
+            .args(args)
+            .current_dir(self.path())
+            .env_clear()
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .env("HOME", self.path())
+            .env("USER", user)
+            .stdin(Stdio::null());
+        command
+    }
+
+    /// Runs `git home args` and returns what it exited with and printed.
+    pub fn try_run(&self, args: &[&str]) -> Output {
+        self.command(args).output().expect("could not run git home")
+    }
+
+    /// Runs `git home args`, failing the test unless it succeeds. Returns
+    /// what it printed.
+    pub fn run(&self, args: &[&str]) -> String {
+        let output = self.try_run(args);
+        assert!(
+            output.status.success(),
+            "git home {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Writes `contents` to `path`, relative to $HOME, creating its directory.
+    pub fn write(&self, path: &str, contents: &str) {
+        let path = self.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("could not create directory");
+        }
+        fs::write(path, contents).expect("could not write file");
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.join(path)).expect("could not read file")
+    }
+
+    /// The backup directory git home moved files into, failing the test
+    /// unless it made exactly one.
+    pub fn backup_dir(&self) -> PathBuf {
+        let dirs: Vec<PathBuf> = fs::read_dir(self.join(GIT_HOME_BACKUP_DIR))
+            .expect("nothing was backed up")
+            .map(|entry| entry.expect("could not read backup directory").path())
+            .collect();
+        assert_eq!(
+            dirs.len(),
+            1,
+            "expected one backup directory, found {:?}",
+            dirs
+        );
+        dirs.into_iter().next().unwrap()
+    }
+
+    /// Opens the home repo.
+    pub fn repo(&self) -> Repository {
+        Repository::open_bare(self.join(".config/git_home")).expect("could not open the home repo")
+    }
+
+    /// The contents of `path` in the index.
+    pub fn staged(&self, path: &str) -> Vec<u8> {
+        let repo = self.repo();
+        let index = repo.index().expect("could not read the index");
+        let entry = index
+            .get_path(Path::new(path), 0)
+            .expect("file is not in the index");
+        let blob = repo.find_blob(entry.id).expect("could not read blob");
+        blob.content().to_vec()
+    }
+}
@@ -0,0 +1,99 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+use std::fs;
+
+/// A home repo with two commits of `.bashrc`, "one" then "two".
+fn committed_home() -> TestHome {
+    let home = TestHome::init();
+    home.write(".bashrc", "one\n");
+    home.run(&["add", ".bashrc"]);
+    home.run(&["commit", "-m", "One"]);
+    home.write(".bashrc", "two\n");
+    home.run(&["add", ".bashrc"]);
+    home.run(&["commit", "-m", "Two"]);
+    home
+}
+
+#[test]
+fn diff_shows_unstaged_changes() {
+    let home = committed_home();
+    assert_eq!(home.run(&["diff"]), "");
+    home.write(".bashrc", "three\n");
+
+    let output = home.run(&["diff"]);
+    assert!(output.starts_with("diff --git $HOME/.bashrc $HOME/.bashrc\n"));
+    assert!(output.ends_with("@@ -1 +1 @@\n-two\n+three\n"));
+    assert_eq!(home.run(&["diff", "--staged"]), "");
+}
+
+#[test]
+fn diff_staged_shows_the_index() {
+    let home = committed_home();
+    home.write(".bashrc", "three\n");
+    home.run(&["add", ".bashrc"]);
+
+    assert_eq!(home.run(&["diff"]), "");
+    assert!(home.run(&["diff", "--staged"]).ends_with("-two\n+three\n"));
+    assert_eq!(
+        home.run(&["diff", "--cached"]),
+        home.run(&["diff", "--staged"])
+    );
+}
+
+#[test]
+fn diff_compares_commits() {
+    let home = committed_home();
+    assert!(home
+        .run(&["diff", "HEAD~1", "HEAD"])
+        .ends_with("-one\n+two\n"));
+    assert!(home
+        .run(&["diff", "HEAD", "HEAD~1"])
+        .ends_with("-two\n+one\n"));
+    home.write(".bashrc", "three\n");
+    assert!(home.run(&["diff", "HEAD~1"]).ends_with("-one\n+three\n"));
+}
+
+#[test]
+fn diff_limits_to_paths() {
+    let home = committed_home();
+    home.write(".profile", "one\n");
+    home.run(&["add", ".profile"]);
+    home.run(&["commit", "-m", "Add .profile"]);
+    home.write(".bashrc", "three\n");
+    home.write(".profile", "two\n");
+
+    let output = home.run(&["diff", ".profile"]);
+    assert!(output.contains("$HOME/.profile"));
+    assert!(!output.contains("$HOME/.bashrc"));
+}
+
+#[test]
+fn diff_names_deleted_files_relative_to_home() {
+    let home = committed_home();
+    fs::remove_file(home.join(".bashrc")).unwrap();
+
+    let output = home.run(&["diff", "./.bashrc"]);
+    assert!(output.contains("deleted file mode 100644\n"));
+    assert!(output.ends_with("-two\n"));
+
+    let output = home.try_run(&["diff", "../.bashrc"]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not in your home directory"));
+}
@@ -0,0 +1,65 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+use std::fs;
+
+/// Runs `git home doctor`, returning its exit code and what it printed.
+fn doctor(home: &TestHome) -> (Option<i32>, String) {
+    let output = home.try_run(&["doctor"]);
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    )
+}
+
+#[test]
+fn doctor_passes_on_a_working_repo() {
+    let home = TestHome::init();
+    home.write(".bashrc", "alias ll='ls -l'\n");
+    home.run(&["add", ".bashrc"]);
+    home.run(&["commit", "-m", "Add .bashrc"]);
+
+    let (code, stdout) = doctor(&home);
+    assert_eq!(code, Some(0), "{}", stdout);
+}
+
+#[test]
+fn doctor_fails_on_a_locked_repo() {
+    let home = TestHome::init();
+    fs::write(home.join(".config/git_home/index.lock"), "").unwrap();
+
+    let (code, stdout) = doctor(&home);
+    assert_eq!(code, Some(3), "{}", stdout);
+    let locks = stdout
+        .lines()
+        .find(|line| line.starts_with("fail  locks"))
+        .expect("the locks check did not fail");
+    assert!(locks.contains("index.lock"), "{}", stdout);
+}
+
+#[test]
+fn doctor_fails_without_a_repo() {
+    let home = TestHome::new();
+
+    let (code, stdout) = doctor(&home);
+    assert_eq!(code, Some(3), "{}", stdout);
+    assert!(stdout.contains("\nfail  repo "), "{}", stdout);
+    // Checks needing the repo are skipped.
+    assert!(!stdout.contains("locks"), "{}", stdout);
+}
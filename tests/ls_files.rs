@@ -0,0 +1,106 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+
+/// A home repo with four files committed and `.profile` staged since.
+fn fixture() -> TestHome {
+    let home = TestHome::init();
+    home.write(".bashrc", "a\n");
+    home.write(".config/app/a.conf", "aa\n");
+    home.write(".config/app/c.conf", "c\n");
+    home.write(".config/b/b.conf", "b\n");
+    home.run(&[
+        "add",
+        ".bashrc",
+        ".config/app/a.conf",
+        ".config/app/c.conf",
+        ".config/b/b.conf",
+    ]);
+    home.run(&["commit", "-m", "Add dotfiles"]);
+    home.write(".profile", "new\n");
+    home.run(&["add", ".profile"]);
+    home
+}
+
+#[test]
+fn ls_files_lists_the_index() {
+    let home = fixture();
+    assert_eq!(
+        home.run(&["ls-files"]),
+        "$HOME/.bashrc\n\
+         $HOME/.config/app/a.conf\n\
+         $HOME/.config/app/c.conf\n\
+         $HOME/.config/b/b.conf\n\
+         $HOME/.profile\n"
+    );
+    assert_eq!(
+        home.run(&["ls-files", "-l", "HEAD"]),
+        "100644        2  $HOME/.bashrc\n\
+         100644        3  $HOME/.config/app/a.conf\n\
+         100644        2  $HOME/.config/app/c.conf\n\
+         100644        2  $HOME/.config/b/b.conf\n"
+    );
+    assert_eq!(
+        home.run(&["ls-files", "-z", "HEAD"]),
+        ".bashrc\0.config/app/a.conf\0.config/app/c.conf\0.config/b/b.conf\0"
+    );
+}
+
+#[test]
+fn ls_files_prints_a_tree() {
+    let home = fixture();
+    assert_eq!(
+        home.run(&["ls-files", "--tree"]),
+        "$HOME\n\
+         ├── .bashrc\n\
+         ├── .config\n\
+         │   ├── app\n\
+         │   │   ├── a.conf\n\
+         │   │   └── c.conf\n\
+         │   └── b\n\
+         │       └── b.conf\n\
+         └── .profile\n"
+    );
+    assert_eq!(
+        home.run(&["ls-files", "--tree", "HEAD"]),
+        "$HOME\n\
+         ├── .bashrc\n\
+         └── .config\n\
+         \x20   ├── app\n\
+         \x20   │   ├── a.conf\n\
+         \x20   │   └── c.conf\n\
+         \x20   └── b\n\
+         \x20       └── b.conf\n"
+    );
+}
+
+#[test]
+fn ls_files_filters_changes_in_home() {
+    let home = fixture();
+    home.write(".bashrc", "changed\n");
+    std::fs::remove_file(home.join(".config/b/b.conf")).unwrap();
+
+    assert_eq!(home.run(&["ls-files", "--modified"]), "$HOME/.bashrc\n");
+    assert_eq!(
+        home.run(&["ls-files", "--deleted"]),
+        "$HOME/.config/b/b.conf\n"
+    );
+    let output = home.try_run(&["ls-files", "--tree", "-z"]);
+    assert_eq!(output.status.code(), Some(64));
+}
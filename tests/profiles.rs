@@ -0,0 +1,87 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+
+/// A home repo with a shared `.bashrc` and a profile matching every host
+/// whose overlay replaces it and adds `.only`.
+fn overlaid_home() -> TestHome {
+    let home = TestHome::init();
+    home.write(".bashrc", "shared\n");
+    home.write(".config/hosts/all/.bashrc", "host\n");
+    home.write(".config/hosts/all/.only", "only\n");
+    home.run(&["add", ".bashrc", ".config/hosts/all"]);
+    home.run(&["commit", "-m", "Add .bashrc"]);
+    let mut config = home.repo().config().unwrap();
+    config.set_str("profile.all.host", "*").unwrap();
+    config
+        .set_str("profile.all.overlay", ".config/hosts/all")
+        .unwrap();
+    home
+}
+
+#[test]
+fn overlay_replaces_files_on_checkout() {
+    let home = overlaid_home();
+    assert!(home
+        .run(&["profile"])
+        .contains("Overlay: $HOME/.config/hosts/all\n"));
+    home.run(&["reset", "--hard", "--confirm"]);
+
+    assert_eq!(home.read(".bashrc"), "host\n");
+    assert_eq!(home.read(".only"), "only\n");
+    // .only comes from the overlay, so it is not untracked.
+    assert_eq!(
+        home.run(&["status", "--porcelain", "-u"]),
+        "## master\n?? .gitconfig\n"
+    );
+}
+
+#[test]
+fn add_stages_overlaid_files_into_the_overlay() {
+    let home = overlaid_home();
+    home.run(&["reset", "--hard", "--confirm"]);
+    home.write(".bashrc", "changed\n");
+    assert_eq!(
+        home.run(&["status", "--porcelain"]),
+        "## master\n M .bashrc\n"
+    );
+
+    home.run(&["add", ".bashrc"]);
+    assert_eq!(home.staged(".bashrc"), b"shared\n");
+    assert_eq!(home.staged(".config/hosts/all/.bashrc"), b"changed\n");
+    assert_eq!(home.read(".config/hosts/all/.bashrc"), "changed\n");
+    assert_eq!(
+        home.run(&["status", "--porcelain"]),
+        "## master\nM  .config/hosts/all/.bashrc\n"
+    );
+}
+
+#[test]
+fn shared_files_are_kept_without_a_profile() {
+    let home = overlaid_home();
+    home.repo()
+        .config()
+        .unwrap()
+        .remove("profile.all.overlay")
+        .unwrap();
+    home.run(&["reset", "--hard", "--confirm"]);
+
+    assert_eq!(home.read(".bashrc"), "shared\n");
+    assert!(!home.join(".only").exists());
+}
@@ -0,0 +1,168 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+use git2::{BranchType, Repository, Signature};
+use std::fs;
+use tempfile::TempDir;
+
+/// An empty bare repo to push to, and its file:// url.
+fn bare_remote() -> (TempDir, String) {
+    let dir = tempfile::tempdir().expect("could not create the remote");
+    Repository::init_bare(dir.path()).expect("could not create the remote");
+    let url = format!("file://{}", dir.path().display());
+    (dir, url)
+}
+
+/// A home repo with `.bashrc` committed and pushed to `url`.
+fn pushed_home(url: &str) -> TestHome {
+    let home = TestHome::init();
+    home.repo()
+        .remote("origin", url)
+        .expect("could not add the remote");
+    home.write(".bashrc", "alias ll='ls -l'\n");
+    home.run(&["add", ".bashrc"]);
+    home.run(&["commit", "-m", "Track .bashrc"]);
+    home.run(&["push"]);
+    home
+}
+
+fn commit_file(home: &TestHome, path: &str, contents: &str) {
+    home.write(path, contents);
+    home.run(&["add", path]);
+    home.run(&["commit", "-m", &format!("Change {}", path)]);
+}
+
+/// Commits `contents` to `path` on the remote's HEAD, as a push from another
+/// machine would.
+fn commit_to_remote(remote: &TempDir, path: &str, contents: &str) {
+    let repo = Repository::open_bare(remote.path()).unwrap();
+    let parent = repo.head().unwrap().peel_to_commit().unwrap();
+    let blob = repo.blob(contents.as_bytes()).unwrap();
+    let mut tree = repo.treebuilder(Some(&parent.tree().unwrap())).unwrap();
+    tree.insert(path, blob, 0o100644).unwrap();
+    let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+    let sig = Signature::now("Other", "other@example.com").unwrap();
+    repo.commit(
+        Some("HEAD"),
+        &sig,
+        &sig,
+        &format!("Change {}", path),
+        &tree,
+        &[&parent],
+    )
+    .unwrap();
+}
+
+#[test]
+fn push_sets_upstream() {
+    let (remote, url) = bare_remote();
+    let home = pushed_home(&url);
+
+    let remote = Repository::open_bare(remote.path()).unwrap();
+    let branch = home.repo().head().unwrap().shorthand().unwrap().to_string();
+    let pushed = remote.find_branch(&branch, BranchType::Local).unwrap();
+    assert_eq!(pushed.get().target(), home.repo().head().unwrap().target());
+    let config = home.repo().config().unwrap();
+    assert_eq!(
+        config
+            .get_string(&format!("branch.{}.remote", branch))
+            .unwrap(),
+        "origin"
+    );
+}
+
+#[test]
+fn push_refuses_without_commits() {
+    let (_remote, url) = bare_remote();
+    let home = TestHome::init();
+    home.repo().remote("origin", &url).unwrap();
+
+    let output = home.try_run(&["push"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Nothing to push"));
+}
+
+#[test]
+fn pull_fast_forwards() {
+    let (remote, url) = bare_remote();
+    let home = pushed_home(&url);
+    commit_to_remote(&remote, ".bashrc", "alias la='ls -a'\n");
+
+    home.run(&["pull"]);
+    assert_eq!(home.read(".bashrc"), "alias la='ls -a'\n");
+    let remote = Repository::open_bare(remote.path()).unwrap();
+    assert_eq!(
+        home.repo().head().unwrap().target(),
+        remote.head().unwrap().target()
+    );
+}
+
+#[test]
+fn pull_merges_diverged_branches() {
+    let (remote, url) = bare_remote();
+    let home = pushed_home(&url);
+    commit_to_remote(&remote, ".vimrc", "set number\n");
+    commit_file(&home, ".profile", "export EDITOR=vim\n");
+
+    home.run(&["pull"]);
+    assert_eq!(home.read(".vimrc"), "set number\n");
+    assert_eq!(home.read(".profile"), "export EDITOR=vim\n");
+    let repo = home.repo();
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(head.parent_count(), 2);
+}
+
+#[test]
+fn pull_refuses_to_overwrite_changes() {
+    let (remote, url) = bare_remote();
+    let home = pushed_home(&url);
+    commit_to_remote(&remote, ".bashrc", "alias la='ls -a'\n");
+    home.write(".bashrc", "# not committed yet\n");
+
+    let output = home.try_run(&["pull"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(".bashrc"));
+    assert_eq!(home.read(".bashrc"), "# not committed yet\n");
+}
+
+#[test]
+fn clone_backs_up_existing_files() {
+    let (_remote, url) = bare_remote();
+    pushed_home(&url);
+
+    let home = TestHome::new();
+    home.write(".bashrc", "# from the distro\n");
+    home.run(&["clone", &url]);
+
+    assert_eq!(home.read(".bashrc"), "alias ll='ls -l'\n");
+    let backups: Vec<_> = fs::read_dir(home.join(".local/share/git_home/backup"))
+        .expect("nothing was backed up")
+        .flatten()
+        .collect();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(
+        fs::read_to_string(backups[0].path().join(".bashrc")).unwrap(),
+        "# from the distro\n"
+    );
+    let config = home.repo().config().unwrap();
+    assert_eq!(
+        config.get_string("status.showUntrackedFiles").unwrap(),
+        "no"
+    );
+}
@@ -0,0 +1,75 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+use std::fs;
+
+/// A home repo with `.bashrc` and `.profile` committed as "one".
+fn committed_home() -> TestHome {
+    let home = TestHome::init();
+    home.write(".bashrc", "one\n");
+    home.write(".profile", "one\n");
+    home.run(&["add", ".bashrc", ".profile"]);
+    home.run(&["commit", "-m", "Add dotfiles"]);
+    home
+}
+
+#[test]
+fn reset_hard_needs_confirm() {
+    let home = committed_home();
+    home.write(".bashrc", "two\n");
+
+    let output = home.try_run(&["reset", "--hard"]);
+    assert_eq!(output.status.code(), Some(64));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--confirm"));
+    assert_eq!(home.read(".bashrc"), "two\n");
+}
+
+#[test]
+fn reset_hard_backs_up_dirty_and_staged_files() {
+    let home = committed_home();
+    home.write(".bashrc", "unstaged\n");
+    home.write(".profile", "staged\n");
+    home.run(&["add", ".profile"]);
+
+    let output = home.run(&["reset", "--hard", "--confirm"]);
+    let backup_dir = home.backup_dir();
+    assert!(output.contains(&format!(
+        "Local changes saved to {}\n",
+        backup_dir.display()
+    )));
+    assert_eq!(home.read(".bashrc"), "one\n");
+    assert_eq!(home.read(".profile"), "one\n");
+    assert_eq!(
+        fs::read_to_string(backup_dir.join(".bashrc")).unwrap(),
+        "unstaged\n"
+    );
+    assert_eq!(
+        fs::read_to_string(backup_dir.join(".profile")).unwrap(),
+        "staged\n"
+    );
+    assert_eq!(home.staged(".profile"), b"one\n");
+}
+
+#[test]
+fn reset_hard_without_changes_backs_up_nothing() {
+    let home = committed_home();
+    let output = home.run(&["reset", "--hard", "--confirm"]);
+    assert!(!output.contains("Local changes saved"));
+    assert!(!home.join(git_home::GIT_HOME_BACKUP_DIR).exists());
+}
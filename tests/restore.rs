@@ -0,0 +1,77 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+use std::fs;
+
+#[test]
+fn restore_backs_up_and_restores_a_modified_file() {
+    let home = TestHome::init();
+    home.write(".bashrc", "committed\n");
+    home.run(&["add", ".bashrc"]);
+    home.run(&["commit", "-m", "Add .bashrc"]);
+    home.write(".bashrc", "modified\n");
+
+    let output = home.run(&["restore", ".bashrc"]);
+    let backup_dir = home.backup_dir();
+    assert_eq!(
+        output,
+        format!(
+            "Restored $HOME/.bashrc\nPrevious versions saved to {}\n",
+            backup_dir.display()
+        )
+    );
+    assert_eq!(home.read(".bashrc"), "committed\n");
+    assert_eq!(
+        fs::read_to_string(backup_dir.join(".bashrc")).unwrap(),
+        "modified\n"
+    );
+}
+
+#[test]
+fn restore_from_a_commit() {
+    let home = TestHome::init();
+    home.write(".bashrc", "first\n");
+    home.run(&["add", ".bashrc"]);
+    home.run(&["commit", "-m", "First"]);
+    home.write(".bashrc", "second\n");
+    home.run(&["add", ".bashrc"]);
+    home.run(&["commit", "-m", "Second"]);
+
+    home.run(&["restore", "--source", "HEAD~1", ".bashrc"]);
+    assert_eq!(home.read(".bashrc"), "first\n");
+    assert_eq!(
+        fs::read_to_string(home.backup_dir().join(".bashrc")).unwrap(),
+        "second\n"
+    );
+    assert_eq!(home.staged(".bashrc"), b"second\n");
+}
+
+#[test]
+fn restore_leaves_unchanged_files_alone() {
+    let home = TestHome::init();
+    home.write(".bashrc", "committed\n");
+    home.run(&["add", ".bashrc"]);
+    home.run(&["commit", "-m", "Add .bashrc"]);
+
+    assert_eq!(
+        home.run(&["restore", ".bashrc"]),
+        "Restored $HOME/.bashrc\n"
+    );
+    assert!(!home.join(git_home::GIT_HOME_BACKUP_DIR).exists());
+}
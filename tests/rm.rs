@@ -0,0 +1,89 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+use std::path::Path;
+
+/// A home repo with `.bashrc` and `.config/app/a.conf` committed.
+fn committed_home() -> TestHome {
+    let home = TestHome::init();
+    home.write(".bashrc", "alias ll='ls -l'\n");
+    home.write(".config/app/a.conf", "a\n");
+    home.run(&["add", ".bashrc", ".config/app/a.conf"]);
+    home.run(&["commit", "-m", "Add dotfiles"]);
+    home
+}
+
+fn is_tracked(home: &TestHome, path: &str) -> bool {
+    let index = home.repo().index().unwrap();
+    index.get_path(Path::new(path), 0).is_some()
+}
+
+#[test]
+fn rm_untracks_and_keeps_the_file() {
+    let home = committed_home();
+    assert_eq!(home.run(&["rm", ".bashrc"]), "rm '$HOME/.bashrc'\n");
+
+    assert!(!is_tracked(&home, ".bashrc"));
+    assert_eq!(home.read(".bashrc"), "alias ll='ls -l'\n");
+    assert_eq!(
+        home.run(&["status", "--porcelain"]),
+        "## master\nD  .bashrc\n"
+    );
+}
+
+#[test]
+fn rm_delete_also_removes_the_file() {
+    let home = committed_home();
+    home.run(&["rm", "--delete", ".bashrc"]);
+
+    assert!(!is_tracked(&home, ".bashrc"));
+    assert!(!home.join(".bashrc").exists());
+    // The last of --cached and --delete wins.
+    home.run(&["rm", "--delete", "--cached", ".config/app/a.conf"]);
+    assert!(home.join(".config/app/a.conf").exists());
+}
+
+#[test]
+fn rm_untracks_directories() {
+    let home = committed_home();
+    assert_eq!(
+        home.run(&["rm", ".config/app"]),
+        "rm '$HOME/.config/app/a.conf'\n"
+    );
+    assert!(!is_tracked(&home, ".config/app/a.conf"));
+    assert!(is_tracked(&home, ".bashrc"));
+}
+
+#[test]
+fn rm_refuses_untracked_files() {
+    let home = committed_home();
+    home.write(".profile", "\n");
+
+    let output = home.try_run(&["rm", ".bashrc", ".profile"]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("$HOME/.profile is not tracked"));
+    // Nothing is removed if any of the files is not tracked.
+    assert!(is_tracked(&home, ".bashrc"));
+}
+
+#[test]
+fn rm_needs_a_file() {
+    let home = committed_home();
+    assert_eq!(home.try_run(&["rm"]).status.code(), Some(64));
+}
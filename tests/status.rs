@@ -0,0 +1,181 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+use std::fs;
+
+/// A home repo tracking `.bashrc` and `.vimrc` with one unpushed commit,
+/// a modified `.bashrc`, a staged new `.profile` and a `.vimrc` changed
+/// both in the index and after.
+fn changed_home() -> (TestHome, String) {
+    let home = TestHome::init();
+    home.write(".bashrc", "alias ll='ls -l'\n");
+    home.write(".vimrc", "set number\n");
+    home.run(&["add", ".bashrc", ".vimrc"]);
+    home.run(&["commit", "-m", "Track dotfiles"]);
+
+    // An upstream that is one commit behind.
+    let repo = home.repo();
+    repo.remote("origin", "file:///nonexistent").unwrap();
+    let head = repo.head().unwrap();
+    let branch = String::from(head.shorthand().unwrap());
+    repo.reference(
+        &format!("refs/remotes/origin/{}", branch),
+        head.target().unwrap(),
+        true,
+        "test",
+    )
+    .unwrap();
+    let mut config = repo.config().unwrap();
+    config
+        .set_str(&format!("branch.{}.remote", branch), "origin")
+        .unwrap();
+    config
+        .set_str(
+            &format!("branch.{}.merge", branch),
+            &format!("refs/heads/{}", branch),
+        )
+        .unwrap();
+    home.write(".inputrc", "set bell-style none\n");
+    home.run(&["add", ".inputrc"]);
+    home.run(&["commit", "-m", "Track .inputrc"]);
+
+    home.write(".bashrc", "alias ll='ls -la'\n");
+    home.write(".profile", "export EDITOR=vim\n");
+    home.run(&["add", ".profile"]);
+    home.write(".vimrc", "set nonumber\n");
+    home.run(&["add", ".vimrc"]);
+    home.write(".vimrc", "set relativenumber\n");
+    home.write(".tmux.conf", "set -g mouse on\n");
+    (home, branch)
+}
+
+#[test]
+fn porcelain_status() {
+    let (home, branch) = changed_home();
+    assert_eq!(
+        home.run(&["status", "--porcelain"]),
+        format!("## {branch}...origin/{branch} [ahead 1]\n M .bashrc\nA  .profile\nMM .vimrc\n")
+    );
+}
+
+#[test]
+fn porcelain_status_lists_untracked_files() {
+    let (home, branch) = changed_home();
+    let output = home.run(&["status", "--porcelain", "-u"]);
+    assert_eq!(
+        output
+            .lines()
+            .filter(|x| x.starts_with("??"))
+            .collect::<Vec<_>>(),
+        vec!["?? .gitconfig", "?? .tmux.conf"]
+    );
+    assert!(output.starts_with(&format!("## {branch}...")));
+}
+
+#[test]
+fn null_terminated_status() {
+    let (home, branch) = changed_home();
+    home.run(&["rm", "--cached", ".inputrc"]);
+    fs::rename(home.join(".inputrc"), home.join(".inputrc.old")).unwrap();
+    home.run(&["add", ".inputrc.old"]);
+    assert_eq!(
+        home.run(&["status", "-z"]),
+        format!("## {branch}...origin/{branch} [ahead 1]\0 M .bashrc\0R  .inputrc.old\0.inputrc\0A  .profile\0MM .vimrc\0")
+    );
+}
+
+#[test]
+fn json_status() {
+    let (home, branch) = changed_home();
+    let file = |path: &str, index: &str, workdir: &str| {
+        format!("{{\"path\":\"{path}\",\"old_path\":null,\"index\":{index},\"workdir\":{workdir}}}")
+    };
+    let files = [
+        file(".bashrc", "null", "\"modified\""),
+        file(".profile", "\"new\"", "null"),
+        file(".vimrc", "\"modified\"", "\"modified\""),
+    ];
+    assert_eq!(
+        home.run(&["status", "--json"]),
+        format!(
+            "{{\"branch\":\"{branch}\",\"upstream\":\"origin/{branch}\",\"ahead\":1,\"behind\":0,\"files\":[{}]}}\n",
+            files.join(",")
+        )
+    );
+}
+
+#[test]
+fn json_status_without_upstream() {
+    let home = TestHome::init();
+    let output = home.run(&["status", "--json", "-u"]);
+    assert!(output.contains("\"upstream\":null,\"ahead\":null,\"behind\":null,\"files\":[]"));
+    assert!(output.ends_with("\"untracked\":[]}\n"));
+}
+
+#[test]
+fn porcelain_and_json_conflict() {
+    let home = TestHome::init();
+    let output = home.try_run(&["status", "--porcelain", "--json"]);
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn porcelain_status_quotes_paths() {
+    let home = TestHome::init();
+    home.write("my file", "one\n");
+    home.run(&["add", "my file"]);
+    home.run(&["commit", "-m", "Track my file"]);
+    home.write("my file", "two\n");
+    home.write("say \"hi\"", "\n");
+    home.write("back\\slash", "\n");
+    home.write("new\nline", "\n");
+    home.write("caf\u{e9}", "\n");
+
+    assert_eq!(
+        home.run(&["status", "--porcelain", "-u"]),
+        "## master\n M \"my file\"\n\
+         ?? .gitconfig\n\
+         ?? \"back\\\\slash\"\n\
+         ?? \"caf\\303\\251\"\n\
+         ?? \"new\\nline\"\n\
+         ?? \"say \\\"hi\\\"\"\n"
+    );
+    assert_eq!(
+        home.run(&["status", "-z", "-u"]),
+        "## master\0 M my file\0?? .gitconfig\0?? back\\slash\0?? caf\u{e9}\0?? new\nline\0?? say \"hi\"\0"
+    );
+
+    home.repo()
+        .config()
+        .unwrap()
+        .set_bool("core.quotePath", false)
+        .unwrap();
+    assert!(home
+        .run(&["status", "--porcelain", "-u"])
+        .contains("?? caf\u{e9}\n"));
+}
+
+#[test]
+fn human_status_gives_paths_from_home() {
+    let (home, _) = changed_home();
+    let output = home.run(&["status"]);
+    assert!(output.contains("\tmodified:   $HOME/.bashrc\n"));
+    assert!(output.contains("\tnew file:   $HOME/.profile\n"));
+    assert!(output.contains("\tmodified:   $HOME/.vimrc\n"));
+}
@@ -0,0 +1,31 @@
+// git-home -- A dotfiles manager using git.
+// Copyright (C) 2022 Jacob Stannix
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod common;
+
+use common::TestHome;
+use git_home::secret::is_encrypted;
+
+#[test]
+fn secret_template_is_stored_encrypted() {
+    let home = TestHome::init();
+    home.write(".tokens", "token for {{ user }}\n");
+    let path = home.join(".tokens");
+    home.run(&["secrets", "add", ".tokens"]);
+    home.run(&["template", "add", path.to_str().unwrap()]);
+
+    assert!(is_encrypted(&home.staged(".tokens")));
+}